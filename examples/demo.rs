@@ -1,3 +1,4 @@
+#![allow(clippy::disallowed_names)]
 use mastring::MAString;
 use std::sync::Mutex;
 
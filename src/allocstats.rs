@@ -0,0 +1,136 @@
+// process wide counters for the alloc-stats feature. The counting functions
+// are always available to the rest of the crate, but do nothing unless the
+// feature is enabled.
+#[cfg(feature = "alloc-stats")]
+use core::sync::atomic::AtomicUsize;
+#[cfg(feature = "alloc-stats")]
+use core::sync::atomic::Ordering;
+
+#[cfg(feature = "alloc-stats")]
+const LENGTH_BUCKETS: usize = usize::BITS as usize + 1;
+
+#[derive(Clone, Copy)]
+pub (super) enum Event {
+    // a heap buffer was allocated to hold string data.
+    #[cfg_attr(not(feature = "alloc"), allow(dead_code))]
+    Buffer = 0,
+    // Clone allocated a seperate control block.
+    BoxedCb,
+    // make_unique copied a buffer because it was shared.
+    CowCopy,
+    // make_unique copied a static string to the heap.
+    StaticPromotion,
+    // reserve moved the data to a larger buffer.
+    Reallocation,
+}
+
+#[cfg(feature = "alloc-stats")]
+const EVENTS: usize = 5;
+
+// an array of atomics can't be built with a repeat expression from a
+// non-const value.
+#[cfg(feature = "alloc-stats")]
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(feature = "alloc-stats")]
+static EVENT_COUNTS: [AtomicUsize; EVENTS] = [ZERO; EVENTS];
+#[cfg(feature = "alloc-stats")]
+static LENGTHS: [AtomicUsize; LENGTH_BUCKETS] = [ZERO; LENGTH_BUCKETS];
+
+#[inline]
+pub (super) fn count(_event: Event) {
+    #[cfg(feature = "alloc-stats")]
+    EVENT_COUNTS[_event as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+// record the length of a newly constructed string.
+#[inline]
+pub (super) fn count_len(_len: usize) {
+    #[cfg(feature = "alloc-stats")]
+    LENGTHS[AllocStats::length_bucket(_len)].fetch_add(1, Ordering::Relaxed);
+}
+
+/// A snapshot of the allocation counters kept with the alloc-stats feature.
+///
+/// The counters are shared by the whole process and are updated with
+/// relaxed atomics, so a snapshot taken while other threads are using
+/// strings is not guaranteed to be consistent between fields.
+#[cfg(feature = "alloc-stats")]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AllocStats {
+    /// The number of heap buffers allocated for string data, this includes
+    /// the buffers counted by cow_copies, static_promotions and
+    /// reallocations, but not buffers adopted from a Vec or String.
+    pub buffers: usize,
+    /// The number of seperate control blocks allocated when cloning a
+    /// uniquely owned string which had no room for an inline one.
+    pub boxed_cbs: usize,
+    /// The number of times a shared buffer was copied so that it could be
+    /// modified.
+    pub cow_copies: usize,
+    /// The number of times a static string was copied to the heap so that
+    /// it could be modified.
+    pub static_promotions: usize,
+    /// The number of times reserve moved a string to a larger buffer.
+    pub reallocations: usize,
+    /// A histogram of the lengths of MAByteStrings and MAStrings
+    /// constructed at runtime from slices, Vecs, Strings and builders,
+    /// indexed by length_bucket. Strings created by from_static are not
+    /// counted.
+    pub lengths: [usize; LENGTH_BUCKETS],
+}
+
+#[cfg(feature = "alloc-stats")]
+impl AllocStats {
+    /// The number of buckets in the length histogram.
+    pub const LENGTH_BUCKETS: usize = LENGTH_BUCKETS;
+
+    /// Returns the bucket of the length histogram that a length is counted
+    /// in. Bucket 0 counts empty strings, and bucket n counts strings whose
+    /// length is at least 2^(n-1) and less than 2^n.
+    pub const fn length_bucket(len: usize) -> usize {
+        (usize::BITS - len.leading_zeros()) as usize
+    }
+
+    /// Returns the current values of the counters.
+    pub fn get() -> Self {
+        let event = |e: Event| EVENT_COUNTS[e as usize].load(Ordering::Relaxed);
+        let mut lengths = [0; LENGTH_BUCKETS];
+        for (l, c) in lengths.iter_mut().zip(LENGTHS.iter()) {
+            *l = c.load(Ordering::Relaxed);
+        }
+        AllocStats {
+            buffers: event(Event::Buffer),
+            boxed_cbs: event(Event::BoxedCb),
+            cow_copies: event(Event::CowCopy),
+            static_promotions: event(Event::StaticPromotion),
+            reallocations: event(Event::Reallocation),
+            lengths: lengths,
+        }
+    }
+
+    /// Sets all the counters back to zero.
+    pub fn reset() {
+        for c in EVENT_COUNTS.iter().chain(LENGTHS.iter()) {
+            c.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the change in the counters since an earlier snapshot, so
+    /// that a workload can be measured without resetting the counters.
+    pub fn since(&self, earlier: &AllocStats) -> AllocStats {
+        let mut lengths = [0; LENGTH_BUCKETS];
+        for (i, l) in lengths.iter_mut().enumerate() {
+            *l = self.lengths[i].wrapping_sub(earlier.lengths[i]);
+        }
+        AllocStats {
+            buffers: self.buffers.wrapping_sub(earlier.buffers),
+            boxed_cbs: self.boxed_cbs.wrapping_sub(earlier.boxed_cbs),
+            cow_copies: self.cow_copies.wrapping_sub(earlier.cow_copies),
+            static_promotions: self.static_promotions.wrapping_sub(earlier.static_promotions),
+            reallocations: self.reallocations.wrapping_sub(earlier.reallocations),
+            lengths: lengths,
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::mem;
+use core::hint;
+use core::ptr;
+use core::fmt;
+
+use crate::MAString;
+
+/// AtomicMAString is a cell holding a MAString, which can be read and
+/// replaced from multiple threads without locking.
+///
+/// Loading the string never blocks and is no more expensive than a Clone,
+/// so loading a short or static string never allocates. Strings are put
+/// into shared ownership mode when they are stored, so that loading them
+/// does not need to allocate a control block. Threads replacing the string
+/// wait for each other and for any loads of the previous value still in
+/// progress.
+pub struct AtomicMAString {
+    // the current value is in slots[current], the other slot is empty
+    // except while a new value is being stored.
+    slots: [UnsafeCell<MaybeUninit<MAString>>; 2],
+    current: AtomicUsize,
+    // number of loads in progress from each slot.
+    readers: [AtomicUsize; 2],
+    writing: AtomicBool,
+}
+
+unsafe impl Send for AtomicMAString {}
+unsafe impl Sync for AtomicMAString {}
+
+impl AtomicMAString {
+    /// Creates a new AtomicMAString, this will not allocate.
+    /// If s is in unique ownership mode, the first load will allocate
+    /// a control block.
+    pub const fn new(s: MAString) -> Self {
+        AtomicMAString {
+            slots: [UnsafeCell::new(MaybeUninit::new(s)), UnsafeCell::new(MaybeUninit::uninit())],
+            current: AtomicUsize::new(0),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            writing: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns a copy of the current value.
+    pub fn load(&self) -> MAString {
+        loop {
+            let i = self.current.load(Ordering::SeqCst);
+            self.readers[i].fetch_add(1, Ordering::SeqCst);
+            let _reader = ReaderGuard(&self.readers[i]);
+            // if a new value was stored between loading current and
+            // registering as a reader, the slot may be about to be emptied.
+            if self.current.load(Ordering::SeqCst) == i {
+                return unsafe { (*(*self.slots[i].get()).as_ptr()).clone() };
+            }
+        }
+    }
+
+    /// Replaces the current value with s.
+    pub fn store(&self, s: MAString) {
+        drop(self.swap(s));
+    }
+
+    /// Replaces the current value with s, returning the previous value.
+    pub fn swap(&self, s: MAString) -> MAString {
+        let _lock = self.lock();
+        unsafe { self.replace(s) }
+    }
+
+    /// Replaces the current value with new if it is equal to current.
+    /// Returns the previous value on success, or the current value on
+    /// failure.
+    pub fn compare_exchange(&self, current: &str, new: MAString) -> Result<MAString, MAString> {
+        let _lock = self.lock();
+        let i = self.current.load(Ordering::SeqCst);
+        // the slot can't change while we hold the lock.
+        unsafe {
+            let cur = &*(*self.slots[i].get()).as_ptr();
+            if &**cur == current {
+                Ok(self.replace(new))
+            } else {
+                Err(cur.clone())
+            }
+        }
+    }
+
+    /// Returns the current value, consuming the AtomicMAString.
+    pub fn into_inner(self) -> MAString {
+        let i = self.current.load(Ordering::Relaxed);
+        let result = unsafe { ptr::read((*self.slots[i].get()).as_ptr()) };
+        mem::forget(self);
+        result
+    }
+
+    // the lock is released when the returned guard is dropped.
+    fn lock(&self) -> WriterGuard<'_> {
+        while self.writing.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            hint::spin_loop();
+        }
+        WriterGuard(&self.writing)
+    }
+
+    // SAFETY: the caller must hold the writer lock.
+    unsafe fn replace(&self, s: MAString) -> MAString {
+        unsafe {
+            // make sure loads don't need to allocate a control block.
+            let long = s.inner.long();
+            if long.len <= isize::MAX as usize && long.cap != 0 {
+                long.get_or_create_cb();
+            }
+            let i = self.current.load(Ordering::SeqCst);
+            let j = 1 - i;
+            // any loads from the empty slot saw that it was not current
+            // and are backing out, so nothing reads it while we write it.
+            (*self.slots[j].get()).as_mut_ptr().write(s);
+            self.current.store(j, Ordering::SeqCst);
+            // wait for loads of the previous value to finish, new loads
+            // will see the new value of current and back out. This is a
+            // read-modify-write rather than a load so that it is ordered
+            // with the readers' fetch_add, a reader that registers after it
+            // synchronizes with it and so sees the new value of current.
+            while self.readers[i].fetch_add(0, Ordering::SeqCst) != 0 {
+                hint::spin_loop();
+            }
+            ptr::read((*self.slots[i].get()).as_ptr())
+        }
+    }
+}
+
+// unregisters a load when dropped, so that a clone that panics (for
+// example on reference count overflow) doesn't leave writers waiting for
+// it forever.
+struct ReaderGuard<'a>(&'a AtomicUsize);
+
+impl Drop for ReaderGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Release);
+    }
+}
+
+// releases the writer lock when dropped.
+struct WriterGuard<'a>(&'a AtomicBool);
+
+impl Drop for WriterGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+impl Drop for AtomicMAString {
+    fn drop(&mut self) {
+        let i = *self.current.get_mut();
+        unsafe { ptr::drop_in_place(self.slots[i].get_mut().as_mut_ptr()); }
+    }
+}
+
+impl Default for AtomicMAString {
+    #[inline]
+    fn default() -> AtomicMAString {
+        Self::new(MAString::new())
+    }
+}
+
+impl fmt::Debug for AtomicMAString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(),fmt::Error> {
+        fmt::Debug::fmt(&self.load(),f)
+    }
+}
+
+#[cfg(all(feature = "alloc", not(any(feature = "refcount-overflow-abort", feature = "refcount-overflow-leak", feature = "refcount-overflow-copy"))))]
+#[test]
+fn test_panic_in_clone() {
+    use std::panic;
+    use std::panic::AssertUnwindSafe;
+    let a = AtomicMAString::new(MAString::from_slice("the quick brown fox jumped over the lazy dog"));
+    let s = a.load();
+    let cbptr = unsafe { s.inner.long().cbptr.load(Ordering::Relaxed) };
+    // pretend a huge number of references have been leaked, so that clone
+    // panics.
+    unsafe { (*cbptr).count.fetch_add(usize::MAX / 4 * 2, Ordering::Relaxed); }
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| a.load())).is_err());
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| a.compare_exchange("foo", MAString::new()))).is_err());
+    unsafe { (*cbptr).count.fetch_sub(usize::MAX / 4 * 2, Ordering::Relaxed); }
+    // neither the reader count nor the writer lock were left behind.
+    a.store(MAString::from_slice("hello"));
+    assert_eq!(a.load(),"hello");
+    assert_eq!(s,"the quick brown fox jumped over the lazy dog");
+}
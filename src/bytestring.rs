@@ -1,6 +1,6 @@
-use core::sync::atomic::AtomicPtr;
-use core::sync::atomic::AtomicUsize;
 use core::sync::atomic::Ordering;
+use crate::sync::AtomicPtr;
+use crate::sync::null_cbptr;
 use core::mem::size_of;
 use core::mem;
 use core::mem::align_of;
@@ -9,24 +9,39 @@ use core::ops::Deref;
 use core::ops::DerefMut;
 use core::ops::Add;
 use core::ops::AddAssign;
+use core::ops::RangeBounds;
 use core::slice;
 use core::cmp::max;
 use core::borrow::Borrow;
 use core::hash::Hasher;
 use core::hash::Hash;
 
+#[cfg(feature = "alloc")]
 use alloc::vec::Vec;
-use alloc::boxed::Box;
-use alloc::str;
-use alloc::fmt;
+use crate::TryReserveError;
+use core::str;
+use core::fmt;
 use crate::inner::InnerLong;
+use crate::inner::ControlBlock;
 use crate::inner::InnerShort;
 use crate::inner::InnerNiche;
 use crate::MAByteStringBuilder;
 use crate::inner::SHORTLEN;
+use crate::inner::infallible;
+use crate::inner::refcount_overflow;
+use crate::inner::Overflow;
+use crate::allocstats;
+use crate::allocstats::Event;
+use crate::split::SplitPattern;
+use crate::split::SplitShared;
+use crate::split::LinesShared;
+use crate::split::SplitWhitespaceShared;
+use crate::MAWeakByteString;
+use crate::Mode;
+use crate::Layout;
+use crate::layout;
 
 
-///
 #[allow(dead_code)]
 #[repr(transparent)]
 pub struct MAByteString {
@@ -59,7 +74,7 @@ impl MAByteString {
     }
 
     #[inline]
-    const fn from_long(long : InnerLong) -> Self {
+    pub (super) const fn from_long(long : InnerLong) -> Self {
         unsafe { mem::transmute(long) }
     }
 
@@ -85,27 +100,64 @@ impl MAByteString {
     }
 
     /// Creates a new MAByteString with a defined capacity, the resulting
-    /// MAByteString will uniquely own it's buffer.
+    /// MAByteString will uniquely own it's buffer. Room is also left for
+    /// an inline control block, so cloning will not allocate.
     pub fn with_capacity(cap: usize) -> Self {
         if cap <= SHORTLEN { return Self::new() }
         Self::from_long(InnerLong::from_slice(b"",true,cap))
     }
 
+    /// Like with_capacity, but returns an error rather than aborting if
+    /// memory allocation fails.
+    pub fn try_with_capacity(cap: usize) -> Result<Self, TryReserveError> {
+        if cap <= SHORTLEN { return Ok(Self::new()) }
+        Ok(Self::from_long(InnerLong::try_from_slice(b"",true,cap)?))
+    }
+
+    /// Like with_capacity, but ensures the string has an inline control
+    /// block, see make_shared.
+    pub fn with_capacity_shared(cap: usize) -> Self {
+        if cap <= SHORTLEN { return Self::new() }
+        let mut inner = InnerLong::from_slice(b"",true,cap);
+        // from_slice only fails to make an inline control block if the
+        // buffer is not aligned, don't let make_shared use up the capacity.
+        inner.make_shared(cap);
+        Self::from_long(inner)
+    }
+
     /// Creates a MAByteString from a slice.
     /// This will allocate if the string cannot be stored as a short string,
     /// the resulting string will be in shared ownership mode with an inline
     /// control block, so cloning will not result in further allocations.
     pub fn from_slice(s: &[u8]) -> Self {
         let len = s.len();
+        allocstats::count_len(len);
         if len <= SHORTLEN {
-            let mut data : [u8; SHORTLEN] = [0; SHORTLEN];
-            data[0..len].copy_from_slice(&s);
-            Self::from_short( InnerShort { data: data, len: len as u8 + 0x80 } )
+            Self::short_from_slice(s)
         } else {
             Self::from_long( InnerLong::from_slice(s, true,0))
         }
     }
 
+    // create a short string, s must fit in one. Unlike from_slice this
+    // doesn't record the length, so it is used when an existing string is
+    // turned into a short string.
+    fn short_from_slice(s: &[u8]) -> Self {
+        let len = s.len();
+        let mut data : [u8; SHORTLEN] = [0; SHORTLEN];
+        data[0..len].copy_from_slice(s);
+        Self::from_short( InnerShort { data: data, len: len as u8 + 0x80 } )
+    }
+
+    /// Like from_slice, but returns an error rather than aborting if
+    /// memory allocation fails.
+    pub fn try_from_slice(s: &[u8]) -> Result<Self, TryReserveError> {
+        if s.len() <= SHORTLEN { return Ok(Self::from_slice(s)) }
+        let result = Self::from_long( InnerLong::try_from_slice(s, true,0)?);
+        allocstats::count_len(s.len());
+        Ok(result)
+    }
+
 
     /// create a MAByteString from a Vec.
     /// This will not allocate.
@@ -116,9 +168,13 @@ impl MAByteString {
     /// be used to createa shared ownership MAString with an inline control block.
     /// if neither of those are possible, then the MAString will have unique
     /// ownership, until it is first Cloned, at which point it will switch to
-    /// shared ownership with an external control block. 
+    /// shared ownership. Clone installs an inline control block if the
+    /// string has grown enough spare capacity for one by then, otherwise it
+    /// allocates an external control block.
+    #[cfg(feature = "alloc")]
     pub fn from_vec(v: Vec<u8>) -> Self {
         let len = v.len();
+        allocstats::count_len(len);
         if len <= SHORTLEN {
             let mut data : [u8; SHORTLEN] = [0; SHORTLEN];
             data[0..len].copy_from_slice(&v);
@@ -128,6 +184,15 @@ impl MAByteString {
         }
     }
 
+    /// Like from_vec, but if the Vec has no room for an inline control block
+    /// the data is copied to a new buffer which does, see make_shared.
+    #[cfg(feature = "alloc")]
+    pub fn from_vec_shared(v: Vec<u8>) -> Self {
+        let mut result = Self::from_vec(v);
+        result.make_shared();
+        result
+    }
+
     /// Create a MAByteString from a static reference
     /// This function will not allocate, and neither wil
     /// Clones of the MAString thus created.
@@ -140,53 +205,66 @@ impl MAByteString {
                  data[i] = s[i];
                  i += 1;
             }
-            //data[0..len].copy_from_slice(&s); // doesn't work in const fn
+            //data[0..len].copy_from_slice(s); // doesn't work in const fn
             Self::from_short( InnerShort { data: data, len: len as u8 + 0x80 } )
         } else {
-            Self::from_long( InnerLong { len : len, cap: 0, ptr: s.as_ptr() as *mut u8, cbptr: AtomicPtr::new(ptr::null_mut()) })
+            Self::from_long( InnerLong { len : len, cap: 0, ptr: s.as_ptr() as *mut u8, cbptr: null_cbptr() })
         }
     }
 
     pub fn from_builder(b : MAByteStringBuilder) -> Self {
+        allocstats::count_len(b.len());
         unsafe {
             let len = b.long().len;
-            if len > isize::max as usize {
+            if len > isize::MAX as usize {
                 return Self::from_short( b.into_short() );
             }
             let cap = b.long().cap;
             let ptr = b.long().ptr;
             mem::forget(b);
             //check if we have room for a control block.
-            //math wont overflow because a vec is limited to isize,
-            //which has half the range of usize.
+            //align_offset may fail, in which case the string is left
+            //without one, see inline_cb_offset.
             let end = ptr.add(len);
-            let cbstart = len + end.align_offset(align_of::<AtomicUsize>());
-            let cbrequired = cbstart + size_of::<AtomicUsize>();
-            let mut cbptr : * mut AtomicUsize = ptr::null_mut();
+            let cbstart = len.saturating_add(end.align_offset(align_of::<ControlBlock>()));
+            let cbrequired = cbstart.saturating_add(size_of::<ControlBlock>());
+            let mut cbptr : * mut ControlBlock = ptr::null_mut();
             if cbrequired <= cap {
-                cbptr = ptr.add(cbstart) as * mut AtomicUsize;
-                *cbptr = AtomicUsize::new(3);
+                cbptr = ptr.add(cbstart) as * mut ControlBlock;
+                ptr::write(cbptr, ControlBlock::new(3, cap));
             }
             Self::from_long(InnerLong { len : len, cap: cap, ptr: ptr, cbptr: AtomicPtr::new(cbptr) })
         }
     }
 
+    /// Like from_builder, but if the builder has no room for an inline
+    /// control block the data is copied to a new buffer which does, see
+    /// make_shared. This happens if the builder has grown beyond the
+    /// capacity it was created with. This is the way to build a string that
+    /// is guaranteed to have an inline control block, so that cloning it
+    /// never allocates.
+    pub fn from_builder_shared(b : MAByteStringBuilder) -> Self {
+        let mut result = Self::from_builder(b);
+        result.make_shared();
+        result
+    }
+
     /// Return the current mode of the MAByteString (for testing/debugging)
     /// The strings returned from this function are not considred stable, and
     /// changes to them are not considered a semver break.
     pub fn get_mode(&self) -> &'static str {
         unsafe {
             let len = self.long().len;
-            if len > isize::max as usize {  //inline string
+            if len > isize::MAX as usize {  //inline string
                 "short"
             } else if self.long().cap == 0 { // static string
                 "static"
             } else {
-                let cbptr = self.long().cbptr.load(Ordering::Acquire);
+                let cbptr = self.long().load_cb();
                 if cbptr.is_null() {
                     "unique"
                 } else { 
-                    let cbval = (*cbptr).load(Ordering::Relaxed);
+                    let cbval = (*cbptr).count.load(Ordering::Relaxed);
                     //println!("cbval = {}",cbval);
                     if (cbval & 1) == 0 {
                         if cbval <= 3 {
@@ -207,6 +285,70 @@ impl MAByteString {
 
     }
 
+    /// Return the current mode of the MAByteString.
+    pub fn mode(&self) -> Mode {
+        unsafe {
+            let len = self.long().len;
+            if len > isize::MAX as usize {  //inline string
+                Mode::Short
+            } else if self.long().cap == 0 { // static string
+                Mode::Static
+            } else {
+                let cbptr = self.long().load_cb();
+                if cbptr.is_null() {
+                    Mode::Unique
+                } else if ((*cbptr).count.load(Ordering::Relaxed) & 1) == 0 {
+                    Mode::CbOwned
+                } else {
+                    Mode::CbInline
+                }
+            }
+        }
+    }
+
+    /// Returns the number of MAByteStrings sharing the buffer, short, static
+    /// and uniquely owned strings return 1.
+    pub fn strong_count(&self) -> usize {
+        unsafe {
+            let long = self.long();
+            if long.len > isize::MAX as usize || long.cap == 0 { return 1 } //short or static string
+            let cbptr = long.load_cb();
+            if cbptr.is_null() { return 1 }
+            return (*cbptr).count.load(Ordering::Acquire) >> 1;
+        }
+    }
+
+    /// Returns true if the string can be modified without copying it,
+    /// that is if it is a short string, it is uniquely owned, or it is in
+    /// a shared ownership mode but no other strings or weak references
+    /// share the buffer.
+    pub fn is_unique(&self) -> bool {
+        unsafe {
+            let long = self.long();
+            if long.len > isize::MAX as usize { return true } //short string
+            if long.cap == 0 { return false } //static string
+            let cbptr = long.load_cb();
+            if cbptr.is_null() { return true }
+            return (*cbptr).is_unique();
+        }
+    }
+
+    /// Returns the string data if this is a static string.
+    pub fn as_static(&self) -> Option<&'static [u8]> {
+        unsafe {
+            let long = self.long();
+            if long.len > isize::MAX as usize || long.cap != 0 { return None }
+            return Some(slice::from_raw_parts(long.ptr, long.len));
+        }
+    }
+
+    /// Returns true if the two strings point to the same data, as with
+    /// Arc::ptr_eq. Short strings store their data inline, so they are only
+    /// equal to themselves.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        this.as_ptr() == other.as_ptr() && this.len() == other.len()
+    }
+
     /// ensure there is capacity for at least extracap more bytes
     /// beyond the current length of the string
     /// and return the pointer and len, and the flag that indicates
@@ -214,29 +356,72 @@ impl MAByteString {
     /// work in the functions that need to reserve space and
     /// then use it.
     pub (super) fn reserve_extra_internal(&mut self, extracap: usize) -> (*mut u8, usize, bool) {
+        infallible(self.reserve_extra_general(extracap, false))
+    }
+
+    pub (super) fn try_reserve_extra_internal(&mut self, extracap: usize) -> Result<(*mut u8, usize, bool), TryReserveError> {
+        self.reserve_extra_general(extracap, true)
+    }
+
+    fn reserve_extra_general(&mut self, extracap: usize, fallible: bool) -> Result<(*mut u8, usize, bool), TryReserveError> {
         unsafe {
             let mut len = self.long().len;
             let mincap;
             //println!("entering reserve_extra_internal mode={} capacity={}",self.get_mode(),self.capacity());
-            if len > isize::max as usize {  //inline string
+            if len > isize::MAX as usize {  //inline string
                 len = (len >> ((size_of::<usize>() - 1) * 8)) - 0x80;
-                mincap = len + extracap;
+                mincap = len.saturating_add(extracap);
                 if mincap > SHORTLEN {
                     let mincap = max(mincap,SHORTLEN*2);
-                    *self = Self::from_long( InnerLong::from_slice(slice::from_raw_parts(self.short().data.as_ptr(),len),true,mincap)) 
+                    let data = slice::from_raw_parts(self.short().data.as_ptr(),len);
+                    *self = Self::from_long(if fallible {
+                        InnerLong::try_from_slice(data,true,mincap)?
+                    } else {
+                        InnerLong::from_slice(data,true,mincap)
+                    });
                 } else {
                     //println!("returning from reserve_extra_internal mode={} capacity={}",self.get_mode(),self.capacity());
-                    return (self.short_mut().data.as_mut_ptr(),len, true);
+                    return Ok((self.short_mut().data.as_mut_ptr(),len, true));
                 }
             } else {
-                mincap = len + extracap;
-                self.long_mut().make_unique(mincap,true);
+                // saturate so that an excessive capacity is reported by reserve.
+                mincap = len.saturating_add(extracap);
+                if self.make_unique_general(mincap,fallible)? {
+                    return Ok((self.short_mut().data.as_mut_ptr(),len, true));
+                }
                 //println!("called make_unique mode={} capacity={}",self.get_mode(),self.capacity());
-                self.long_mut().reserve(mincap,true);
+                if fallible {
+                    self.long_mut().try_reserve(mincap,true)?;
+                } else {
+                    self.long_mut().reserve(mincap,true);
+                }
                 //println!("returning from reserve_extra_internal mode={} capacity={}",self.get_mode(),self.capacity());
             }
             // if we reach here, we know it's a "long" String.
-            return (self.long().ptr, len, false);
+            return Ok((self.long().ptr, len, false));
+        }
+    }
+
+    // make sure we have unique ownership of the buffer of a long string,
+    // copying it if needed. If the data has to be copied and both it and
+    // mincap fit in a short string, we switch to a short string instead.
+    // Returns true if the string is now short.
+    // SAFETY: the string must not be short.
+    unsafe fn make_unique_general(&mut self, mincap: usize, fallible: bool) -> Result<bool, TryReserveError> {
+        unsafe {
+            let len = self.long().len;
+            if len <= SHORTLEN && mincap <= SHORTLEN && !self.is_unique() {
+                if self.long().cap != 0 { allocstats::count(Event::CowCopy) }
+                let short = Self::short_from_slice(slice::from_raw_parts(self.long().ptr, len));
+                *self = short;
+                return Ok(true);
+            }
+            if fallible {
+                self.long_mut().try_make_unique(mincap,true)?;
+            } else {
+                self.long_mut().make_unique(mincap,true);
+            }
+            Ok(false)
         }
     }
 
@@ -244,26 +429,69 @@ impl MAByteString {
     pub fn reserve(&mut self, mincap: usize) {
         unsafe {
             let mut len = self.long().len;
-            if len > isize::max as usize {  //inline string
+            if len > isize::MAX as usize {  //inline string
                 if mincap > SHORTLEN {
                     len = (len >> ((size_of::<usize>() - 1) * 8)) - 0x80;
                     let mincap = max(mincap,SHORTLEN*2);
                     *self = Self::from_long(InnerLong::from_slice(slice::from_raw_parts(self.short().data.as_ptr(),len),true,mincap))
                 }
-            } else {
-                self.long_mut().make_unique(mincap,true);
+            } else if !infallible(self.make_unique_general(mincap,false)) {
                 self.long_mut().reserve(mincap,true);
             }
         }
     }
 
+    /// Like reserve, but returns an error rather than aborting if memory
+    /// allocation fails.
+    pub fn try_reserve(&mut self, mincap: usize) -> Result<(), TryReserveError> {
+        unsafe {
+            let mut len = self.long().len;
+            if len > isize::MAX as usize {  //inline string
+                if mincap > SHORTLEN {
+                    len = (len >> ((size_of::<usize>() - 1) * 8)) - 0x80;
+                    let mincap = max(mincap,SHORTLEN*2);
+                    *self = Self::from_long(InnerLong::try_from_slice(slice::from_raw_parts(self.short().data.as_ptr(),len),true,mincap)?)
+                }
+            } else if !self.make_unique_general(mincap,true)? {
+                self.long_mut().try_reserve(mincap,true)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Shrinks the capacity of the string as much as possible, while keeping
+    /// it at least mincap. If the string fits in a short string it is
+    /// converted to one, otherwise the buffer is reallocated if it is larger
+    /// than needed for mincap bytes and an inline control block. Strings
+    /// whose buffer is shared are left unchanged, as shrinking them would
+    /// mean copying the data.
+    pub fn shrink_to(&mut self, mincap: usize) {
+        unsafe {
+            let len = self.long().len;
+            if len > isize::MAX as usize || self.long().cap == 0 { return } //short or static string
+            if !self.is_unique() { return }
+            if len <= SHORTLEN && mincap <= SHORTLEN {
+                let short = Self::short_from_slice(self);
+                *self = short;
+            } else {
+                self.long_mut().shrink_to(mincap, true);
+            }
+        }
+    }
+
+    /// Shrinks the capacity of the string as much as possible, see
+    /// shrink_to.
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink_to(0);
+    }
+
     /// report the "capacity" of the string. Be aware that due to MAByteStrings
     /// copy on write model, this does not gaurantee that future operations
     /// will not allocate. Returns zero for static strings.
     pub fn capacity(&self) -> usize {
         unsafe {
             let len = self.long().len;
-            if len > isize::max as usize {  //inline string
+            if len > isize::MAX as usize {  //inline string
                 //println!("short string");
                 SHORTLEN
             } else {
@@ -273,6 +501,35 @@ impl MAByteString {
         }
     }
 
+    /// Returns the number of bytes of heap memory used by the string,
+    /// including any control block. A buffer shared by several strings is
+    /// counted in full for each of them, MemoryReport can be used to count
+    /// each buffer once. Short and static strings return 0.
+    pub fn heap_size(&self) -> usize {
+        unsafe {
+            let long = self.long();
+            if long.len > isize::MAX as usize || long.cap == 0 { return 0 } //short or static string
+            let cbptr = long.load_cb();
+            if cbptr.is_null() { return long.cap }
+            return (*cbptr).heap_size();
+        }
+    }
+
+    /// Returns a snapshot of the internal representation of the string, for
+    /// debugging.
+    pub fn layout(&self) -> Layout {
+        unsafe { layout::layout(self.long()) }
+    }
+
+    /// Checks that the internal representation of the string is valid, and
+    /// panics with a description of the problem and the layout if it is
+    /// not. This is only available in debug builds, or with the
+    /// check-invariants feature.
+    #[cfg(any(debug_assertions, feature = "check-invariants"))]
+    pub fn check_invariants(&self) {
+        unsafe { layout::check_invariants(self.long(), false) }
+    }
+
     // clears the string.
     // if the string is in unique ownership mode, or is in shared ownership
     // mode but we are the only owner then this will reset the length but
@@ -281,7 +538,7 @@ impl MAByteString {
     pub fn clear(&mut self) {
         unsafe {
             let len = self.long().len;
-            if len > isize::max as usize {  //inline string
+            if len > isize::MAX as usize {  //inline string
                 self.short_mut().len = 0x80;
             } else if self.long().cap == 0 { // static string
                 self.short_mut().len = 0x80;
@@ -290,8 +547,7 @@ impl MAByteString {
                 if cbptr.is_null() { // unique ownership mode.
                     self.long_mut().len = 0;
                 } else {
-                    let refcount = (*cbptr).load(Ordering::Relaxed) >> 1;
-                    if refcount == 1 {
+                    if (*cbptr).is_unique() {
                         // we are the only owner of the String
                         self.long_mut().len = 0;
                     } else {
@@ -303,15 +559,29 @@ impl MAByteString {
         }
     }
 
+    /// Ensure that cloning the string will not allocate, by switching a
+    /// uniquely owned string to shared ownership mode with an inline control
+    /// block. If there is no room for the control block after the data,
+    /// the string is copied to a new buffer. Other strings are left as
+    /// they are, since cloning them already does not allocate.
+    pub fn make_shared(&mut self) {
+        unsafe {
+            let len = self.long().len;
+            if len > isize::MAX as usize { return } //inline string
+            self.long_mut().make_shared(0);
+        }
+    }
+
     /// convert the MAByteStirng into a Vec, this may allocate.
+    #[cfg(feature = "alloc")]
     pub fn into_vec(mut self) -> Vec<u8> {
         unsafe {
             let mut len = self.long().len;
-            if len > isize::max as usize {  //inline string
+            if len > isize::MAX as usize {  //inline string
                 len = (len >> ((size_of::<usize>() - 1) * 8)) - 0x80;
                 return slice::from_raw_parts(self.short().data.as_ptr(), len).to_vec();
             }
-            self.long_mut().make_unique(0,true);
+            self.long_mut().make_unique(0,false);
             let cap = self.long().cap;
             let ptr = self.long().ptr;
             mem::forget(self);
@@ -319,11 +589,247 @@ impl MAByteString {
         }
     }
 
+    /// convert the MAByteString into a MAByteStringBuilder if this can be
+    /// done without copying the data, that is if is_unique returns true and
+    /// the string starts at the start of it's buffer, otherwise the string
+    /// is returned unchanged. A string created by the slice methods may not,
+    /// and would have to be moved.
+    pub fn try_unwrap(self) -> Result<MAByteStringBuilder, Self> {
+        // we own self, so if there are no other references to the buffer
+        // nobody else can create one, and from_mabs won't copy.
+        if !self.is_unique() { return Err(self) }
+        if self.mode() != Mode::Short && unsafe { !self.long().at_alloc_start() } { return Err(self) }
+        Ok(MAByteStringBuilder::from_mabs(self))
+    }
+
+    /// convert the MAByteString into a Vec if this can be done without
+    /// copying the data, otherwise the string is returned unchanged. Short
+    /// strings are also returned unchanged, since they have no buffer that
+    /// can be given to the Vec. See try_unwrap.
+    #[cfg(feature = "alloc")]
+    pub fn into_vec_if_unique(self) -> Result<Vec<u8>, Self> {
+        if self.mode() == Mode::Short || !self.is_unique() { return Err(self) }
+        if unsafe { !self.long().at_alloc_start() } { return Err(self) }
+        Ok(self.into_vec())
+    }
+
+    /// Returns a MAByteString containing the given range of this string.
+    /// If the result can be stored as a short string then the data will be
+    /// copied, otherwise the result will share this string's buffer in the
+    /// same way as Clone, so at most a single memory allocation is needed to
+    /// create a control block if this string is in unique ownership mode.
+    /// Panics if the range is out of bounds.
+    pub fn slice<R>(&self, range: R) -> Self
+    where
+        R: RangeBounds<usize>,
+    {
+        let sub = &self.deref()[(range.start_bound().cloned(), range.end_bound().cloned())];
+        unsafe { self.slice_ref_internal(sub) }
+    }
+
+    // create a MAByteString from part of this string's data, sharing
+    // the buffer if the result cannot be stored as a short string.
+    // SAFETY: sub must be a subslice of self.
+    pub (super) unsafe fn slice_ref_internal(&self, sub: &[u8]) -> Self {
+        if sub.len() <= SHORTLEN { return Self::from_slice(sub) }
+        // if we reach here self must be a long string, since sub is too
+        // long to be a short string.
+        let mut result = self.clone();
+        unsafe {
+            let long = result.long_mut();
+            let offset = sub.as_ptr().offset_from(long.ptr) as usize;
+            long.ptr = long.ptr.add(offset);
+            long.len = sub.len();
+            if long.cap != 0 { // static strings have no capacity to adjust.
+                long.cap -= offset;
+            }
+        }
+        result
+    }
+
+    /// Returns an iterator over the pieces of this string seperated by pat.
+    /// The pieces share this string's buffer in the same way as slice.
+    pub fn split_shared<P>(&self, pat: P) -> SplitShared<'_, MAByteString, P>
+    where
+        P: SplitPattern<[u8]>,
+    {
+        SplitShared::new(self, pat)
+    }
+
+    /// Returns an iterator over the lines of this string, which may be ended
+    /// by "\n" or "\r\n". The lines share this string's buffer in the same
+    /// way as slice.
+    pub fn lines_shared(&self) -> LinesShared<'_, MAByteString> {
+        LinesShared::new(self)
+    }
+
+    /// Returns an iterator over the pieces of this string seperated by
+    /// ascii whitespace. The pieces share this string's buffer in the same
+    /// way as slice.
+    pub fn split_whitespace_shared(&self) -> SplitWhitespaceShared<'_, MAByteString> {
+        SplitWhitespaceShared::new(self)
+    }
+
+    /// Splits the string on the first occurrence of pat. The pieces share
+    /// this string's buffer in the same way as slice.
+    pub fn split_once_shared<P>(&self, mut pat: P) -> Option<(MAByteString, MAByteString)>
+    where
+        P: SplitPattern<[u8]>,
+    {
+        let (start, end) = pat.find_in(self)?;
+        unsafe { Some((self.slice_ref_internal(&self[..start]), self.slice_ref_internal(&self[end..]))) }
+    }
+
+    /// Removes the first n bytes from the string without copying the rest
+    /// of the data, unless what remains can be stored as a short string.
+    /// If the string is in unique ownership mode it will be switched to
+    /// shared ownership mode, which requires allocating a control block.
+    /// Panics if n is greater than the length of the string.
+    pub fn advance(&mut self, n: usize) {
+        let len = self.len();
+        assert!(n <= len, "cannot advance past the end of the string");
+        if len - n <= SHORTLEN {
+            // this also makes sure we never end up with a long string with
+            // a capacity of zero, which would be mistaken for a static string.
+            *self = Self::short_from_slice(&self[n..]);
+            return;
+        }
+        unsafe {
+            // if we reach here we know it's a "long" string.
+            let long = self.long_mut();
+            if long.cap != 0 { // static strings have no capacity to adjust.
+                // in unique ownership mode the pointer must be the start
+                // of the allocation, so switch to shared ownership mode.
+                long.get_or_create_cb();
+                long.cap -= n;
+            }
+            long.ptr = long.ptr.add(n);
+            long.len -= n;
+        }
+    }
+
+    /// Splits the string in two at the given index, returning the bytes
+    /// before it and leaving the bytes after it in self. The two halves
+    /// share the buffer in the same way as slice.
+    /// Panics if at is greater than the length of the string.
+    pub fn split_to(&mut self, at: usize) -> Self {
+        let result = self.slice(..at);
+        self.advance(at);
+        result
+    }
+
+    /// Splits the string in two at the given index, returning the bytes
+    /// after it and leaving the bytes before it in self. The two halves
+    /// share the buffer in the same way as slice.
+    /// Panics if at is greater than the length of the string.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        let result = self.slice(at..);
+        self.truncate(at);
+        result
+    }
+
+    /// Shortens the string to len bytes, leaving the capacity and the mode
+    /// of the string unchanged. Does nothing if the string is already
+    /// shorter than len.
+    pub fn truncate(&mut self, len: usize) {
+        unsafe {
+            let oldlen = self.long().len;
+            if oldlen > isize::MAX as usize {  //inline string
+                let oldlen = (oldlen >> ((size_of::<usize>() - 1) * 8)) - 0x80;
+                if len < oldlen { self.short_mut().len = len as u8 + 0x80 }
+            } else if len < oldlen {
+                self.long_mut().len = len;
+            }
+        }
+    }
+
+    /// Creates a weak reference to this string, which can be upgraded back
+    /// to a MAByteString as long as the string or one of it's clones
+    /// is still alive. If the string is in unique ownership mode it will be
+    /// switched to shared ownership mode, which requires allocating a
+    /// control block.
+    pub fn downgrade(&self) -> MAWeakByteString {
+        MAWeakByteString::new(self)
+    }
+
     // Appends a given slice to the end of this bytestring.
     pub fn push_slice(&mut self, bytestring: &[u8]) {
         *self += bytestring;
     }
 
+    /// Like push_slice, but returns an error rather than aborting if
+    /// memory allocation fails.
+    pub fn try_push_slice(&mut self, bytestring: &[u8]) -> Result<(), TryReserveError> {
+        unsafe {
+            let (_, len, short) = self.try_reserve_extra_internal(bytestring.len())?;
+            self.append_internal(len, short, bytestring);
+        }
+        Ok(())
+    }
+
+    // copy other to the end of the string, after reserving space for it.
+    // The pointer is fetched again here, as the one returned when reserving
+    // may point into a short string, and reborrowing self for this call
+    // invalidates it.
+    // SAFETY: len and short must be the result of reserving space for other.
+    unsafe fn append_internal(&mut self, mut len: usize, short: bool, other: &[u8]) {
+        unsafe {
+            let ptr = if short { self.short_mut().data.as_mut_ptr() } else { self.long().ptr };
+            ptr::copy_nonoverlapping(other.as_ptr(), ptr.add(len), other.len());
+            len += other.len();
+            if short {
+                self.short_mut().len = (len + 0x80) as u8;
+            } else {
+                self.long_mut().len = len;
+            }
+        }
+    }
+
+    /// Like clone, but returns an error rather than aborting if memory
+    /// allocation for a control block, or for a copy when the reference
+    /// count overflows, fails.
+    pub fn try_clone(&self) -> Result<Self, TryReserveError> {
+        self.clone_internal(true)
+    }
+
+    // if fallible is false, allocation failures abort and capacity overflows
+    // panic as they do for Vec, so an error is never returned.
+    fn clone_internal(&self, fallible: bool) -> Result<Self, TryReserveError> {
+        unsafe {
+            let len = self.long().len;
+            if len > isize::MAX as usize {  //inline string
+                Ok(MAByteString::from_short(*self.short()))
+            } else if self.long().cap == 0 { // static string
+                Ok(MAByteString::from_long( InnerLong { len : len, cap: 0, ptr: self.long().ptr, cbptr: AtomicPtr::new(ptr::null_mut()) }))
+            } else {
+                let cbptr = if fallible {
+                    self.long().try_get_or_create_cb()?
+                } else {
+                    self.long().get_or_create_cb()
+                };
+                if (*cbptr).count.fetch_add(2, Ordering::Relaxed) > usize::MAX / 2 {
+                    (*cbptr).count.fetch_sub(2, Ordering::Relaxed);
+                    return match refcount_overflow() {
+                        Overflow::Leak => Ok(self.leaked()),
+                        Overflow::Copy if fallible => MAByteString::try_from_slice(self),
+                        Overflow::Copy => Ok(MAByteString::from_slice(self)),
+                    };
+                }
+                Ok(MAByteString::from_long( InnerLong { len : len, cap: self.long().cap, ptr: self.long().ptr, cbptr: AtomicPtr::new(cbptr) }))
+            }
+        }
+    }
+
+    // returns a reference to the buffer in static mode, which does not
+    // count towards the reference count.
+    // SAFETY: the string must be in shared ownership mode, and it's
+    // reference count must be too high to ever get back down to zero.
+    pub (super) unsafe fn leaked(&self) -> Self {
+        unsafe {
+            MAByteString::from_long( InnerLong { len : self.long().len, cap: 0, ptr: self.long().ptr, cbptr: AtomicPtr::new(ptr::null_mut()) })
+        }
+    }
+
     // Joins together an iterator of strings, using self as a seperator.
     pub fn join<T,I>(&self, iter : I) -> Self
     where
@@ -338,38 +844,16 @@ impl Drop for MAByteString {
     fn drop(&mut self) {
         unsafe {
             let len = self.long().len;
-            if len > isize::max as usize { return }; //inline string
+            if len > isize::MAX as usize { return }; //inline string
             drop(ptr::read(self.long_mut())); // call drop for the inner type.
         }
     }
 }
 
 impl Clone for MAByteString{
+    #[inline]
     fn clone(&self) -> Self {
-        unsafe {
-            let len = self.long().len;
-            if len > isize::max as usize {  //inline string
-                MAByteString::from_short(*self.short())
-            } else if self.long().cap == 0 { // static string
-                MAByteString::from_long( InnerLong { len : len, cap: 0, ptr: self.long().ptr, cbptr: AtomicPtr::new(ptr::null_mut()) })
-            } else {
-                let mut cbptr = self.long().cbptr.load(Ordering::Acquire);
-                if cbptr.is_null() {
-                    let newcbptr = Box::into_raw(Box::new(AtomicUsize::new(2)));
-                    if let Err(cxcbptr) = self.long().cbptr.compare_exchange(ptr::null_mut(),newcbptr,Ordering::AcqRel,Ordering::Acquire) {
-                        let _ = Box::from_raw(newcbptr);
-                        cbptr = cxcbptr;
-                    } else  {
-                        cbptr = newcbptr
-                    }
-                }
-                if (*cbptr).fetch_add(2, Ordering::Relaxed) > usize::MAX / 2 {
-                    (*cbptr).fetch_sub(2, Ordering::Relaxed);
-                    panic!("reference count too high, you have a refrence leak");
-                }
-                MAByteString::from_long( InnerLong { len : len, cap: self.long().cap, ptr: self.long().ptr, cbptr: AtomicPtr::new(cbptr) })
-            }
-        }
+        infallible(self.clone_internal(false))
     }
 }
 
@@ -379,7 +863,7 @@ impl Deref for MAByteString {
    fn deref(&self) -> &[u8] {
         unsafe {
             let mut len = self.long().len;
-            let ptr = if len > isize::max as usize {
+            let ptr = if len > isize::MAX as usize {
                 len = (len >> ((size_of::<usize>() - 1) * 8)) - 0x80;
                 self.short().data.as_ptr()
             } else {
@@ -396,11 +880,13 @@ impl DerefMut for MAByteString {
    fn deref_mut(&mut self) -> &mut [u8] {
         unsafe {
             let mut len = self.long().len;
-            let ptr = if len > isize::max as usize {  //inline string
+            let ptr = if len > isize::MAX as usize {  //inline string
                 len = (len >> ((size_of::<usize>() - 1) * 8)) - 0x80;
                 self.short_mut().data.as_mut_ptr()
-            } else { 
-                self.long_mut().make_unique(0,true);
+            } else if infallible(self.make_unique_general(0,false)) {
+                // the data was copied into a short string.
+                self.short_mut().data.as_mut_ptr()
+            } else {
                 // if we get here we have unique owenership of the data
                 // either by being in unique mode, or by being in shared
                 // ownership mode but being the only owner.
@@ -422,14 +908,8 @@ impl Add<&[u8]> for MAByteString {
 impl AddAssign<&[u8]> for MAByteString {
     fn add_assign(&mut self, other: &[u8]) {
         unsafe {
-            let (ptr, mut len, short) = self.reserve_extra_internal(other.len());
-            ptr::copy_nonoverlapping(other.as_ptr(), ptr.add(len), other.len());
-            len += other.len();
-            if short {
-                self.short_mut().len = (len + 0x80) as u8;
-            } else {
-                self.long_mut().len = len;
-            }
+            let (_, len, short) = self.reserve_extra_internal(other.len());
+            self.append_internal(len, short, other);
         }
     }
 }
@@ -441,7 +921,7 @@ pub (super) fn bytes_debug(s: &[u8], f: &mut fmt::Formatter<'_>) -> Result<(),fm
     let len = s.len();
     while p < len {
         let c = s[p];
-        if (c < 0x20) || (c > 0x7E) || (c == b'\\') || (c == b'\"') {
+        if !(0x20..=0x7E).contains(&c) || (c == b'\\') || (c == b'\"') {
             // we found a character that can't be written directly
             // check if there are any characters waiting to be written
             // before the current one
@@ -548,7 +1028,7 @@ impl Hash for MAByteString {
 impl PartialOrd for MAByteString {
     #[inline]
     fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
-        self.deref().partial_cmp(other.deref())
+        Some(self.cmp(other))
     }
 }
 
@@ -559,7 +1039,7 @@ impl Ord for MAByteString {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "alloc"))]
 macro_rules! assert_mode {
     ($s:expr, $expectedmode:expr) => {
         for _n in 1..=1 { // loop for break, since block labels are not supported in rust 1.63
@@ -587,6 +1067,45 @@ fn test_len_transmutation() {
     }
 }
 
+#[cfg(all(feature = "alloc", not(feature = "refcount-overflow-abort")))]
+#[test]
+#[cfg_attr(not(any(feature = "refcount-overflow-leak", feature = "refcount-overflow-copy")), should_panic)]
+fn test_refcount_overflow() {
+    // remove the fake references at the end of the test, even if it panics.
+    struct Restore(*mut ControlBlock);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            unsafe { (*self.0).count.fetch_sub(usize::MAX / 4 * 2, Ordering::Relaxed); }
+        }
+    }
+    let s = MAByteString::from_slice(b"the quick brown fox jumped over the lazy dog");
+    let s2 = s.clone();
+    assert_mode!(s2,"cbinline (shared)");
+    let restore = unsafe { Restore(s.long().cbptr.load(Ordering::Relaxed)) };
+    // pretend a huge number of references have been leaked.
+    unsafe { (*restore.0).count.fetch_add(usize::MAX / 4 * 2, Ordering::Relaxed); }
+    let w = s.downgrade();
+    let s3 = s.clone();
+    let s4 = w.upgrade().unwrap();
+    let s5 = s.try_clone().unwrap();
+    assert_eq!(s3,s);
+    assert_eq!(s4,s);
+    assert_eq!(s5,s);
+    if cfg!(feature = "refcount-overflow-leak") {
+        assert_mode!(s3,"static");
+        assert_mode!(s4,"static");
+        assert_mode!(s5,"static");
+        assert_eq!(s3.as_ptr(),s.as_ptr());
+    } else {
+        assert_mode!(s3,"cbinline (unique)");
+        assert_mode!(s4,"cbinline (unique)");
+        assert_mode!(s5,"cbinline (unique)");
+        assert_ne!(s3.as_ptr(),s.as_ptr());
+        assert_ne!(s5.as_ptr(),s.as_ptr());
+    }
+}
+
+#[cfg(feature = "alloc")]
 #[test]
 fn test_reserve_extra_internal() {
     let mut s = MAByteString::from_static(b"test");
@@ -661,6 +1180,12 @@ fn test_reserve_extra_internal() {
     assert!(s.capacity() <= "the quick brown fox jumped over the lazy dog".len() + 50);
     //s2 now owns the buffer fomerly owned by s
     assert_mode!(s2,"cbinline (unique)");
+    #[cfg(miri)]
+    // with an unaligned vec from miri there may be no room for the control
+    // block after the reserved space, in which case the clone places it
+    // straight after the data.
+    assert!(s2.capacity() >= 100 - mem::size_of::<usize>());
+    #[cfg(not(miri))]
     assert!(s2.capacity() >= 100);
     assert!(s2.capacity() <= 150);
 
@@ -725,6 +1250,7 @@ impl<const N: usize> From<&[u8;N]> for MAByteString {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl From<Vec<u8>> for MAByteString {
     #[inline]
     fn from(s : Vec<u8>) -> Self {
@@ -739,6 +1265,7 @@ impl From<MAByteStringBuilder> for MAByteString {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl From<&Vec<u8>> for MAByteString {
     #[inline]
     fn from(s : &Vec<u8>) -> Self {
@@ -12,16 +12,22 @@ use core::borrow::Borrow;
 use core::hash::Hasher;
 use core::hash::Hash;
 
-extern crate alloc;
+#[cfg(feature = "alloc")]
 use alloc::vec::Vec;
-use alloc::str;
-use alloc::fmt;
+use crate::TryReserveError;
+use core::str;
+use core::fmt;
 use crate::inner::InnerLong;
 use crate::inner::InnerShort;
 use crate::inner::InnerNiche;
 use crate::MAByteString;
+use crate::Mode;
+use crate::Layout;
+use crate::layout;
 use crate::bytestring::bytes_debug;
 use crate::inner::SHORTLEN;
+use crate::inner::free_buffer;
+use crate::inner::infallible;
 
 #[cfg(all(miri,test))]
 use core::sync::atomic::AtomicPtr;
@@ -57,7 +63,7 @@ impl MAByteStringBuilder {
     }
 
     #[inline]
-    const fn from_long(long : InnerLong) -> Self {
+    pub (super) const fn from_long(long : InnerLong) -> Self {
         unsafe { mem::transmute(long) }
     }
 
@@ -82,29 +88,49 @@ impl MAByteStringBuilder {
         Self::from_short( InnerShort { data: [0; SHORTLEN] , len: 0x80 } )
     }
 
-    /// Creates a new MAByteString with a defined capacity
+    /// Creates a new MAByteStringBuilder with a defined capacity. Room is
+    /// also left for an inline control block after cap bytes, so as long as
+    /// the length does not exceed cap, converting the builder to a
+    /// MAByteString will not need to allocate one. If the builder may grow
+    /// beyond cap, use MAByteString::from_builder_shared to make sure the
+    /// string has an inline control block.
     pub fn with_capacity(cap: usize) -> Self {
         if cap <= SHORTLEN { return Self::new() }
         Self::from_long(InnerLong::from_slice(b"",false,cap))
     }
 
+    /// Like with_capacity, but returns an error rather than aborting if
+    /// memory allocation fails.
+    pub fn try_with_capacity(cap: usize) -> Result<Self, TryReserveError> {
+        if cap <= SHORTLEN { return Ok(Self::new()) }
+        Ok(Self::from_long(InnerLong::try_from_slice(b"",false,cap)?))
+    }
+
     /// Creates a MAByteStringBuilder from a slice.
     /// This will allocate if the string cannot be stored as a short string,
     pub fn from_slice(s: &[u8]) -> Self {
         let len = s.len();
         if len <= SHORTLEN {
             let mut data : [u8; SHORTLEN] = [0; SHORTLEN];
-            data[0..len].copy_from_slice(&s);
+            data[0..len].copy_from_slice(s);
             Self::from_short(InnerShort { data: data, len: len as u8 + 0x80 })
         } else {
             Self::from_long(InnerLong::from_slice(s,false,0))
         }
     }
 
+    /// Like from_slice, but returns an error rather than aborting if
+    /// memory allocation fails.
+    pub fn try_from_slice(s: &[u8]) -> Result<Self, TryReserveError> {
+        if s.len() <= SHORTLEN { return Ok(Self::from_slice(s)) }
+        Ok(Self::from_long(InnerLong::try_from_slice(s,false,0)?))
+    }
+
     /// create a MAByteStringBuilder from a Vec.
     /// This will not allocate.
     /// If the string can be represented as a  short string then it will be stored
     /// as one and the memory owned by the Vec will be freed.
+    #[cfg(feature = "alloc")]
     pub fn from_vec(v: Vec<u8>) -> Self {
         let len = v.len();
         if len <= SHORTLEN {
@@ -119,8 +145,11 @@ impl MAByteStringBuilder {
     pub fn from_mabs(mut s: MAByteString) -> Self {
         unsafe {
             let len = s.long().len;
-            if len > isize::max as usize {  //inline string
+            if len > isize::MAX as usize {  //inline string
                 Self::from_short( s.into_short() )
+            } else if len <= SHORTLEN && !s.is_unique() {
+                // the data has to be copied, so use a short string.
+                Self::from_slice(&s)
             } else {
                 s.long_mut().make_unique(0,false);
                 Self::from_long(s.into_long())
@@ -134,7 +163,7 @@ impl MAByteStringBuilder {
     pub fn get_mode(&self) -> &'static str {
         unsafe {
             let len = self.long().len;
-            if len > isize::max as usize {  //inline string
+            if len > isize::MAX as usize {  //inline string
                 "short"
             } else if self.long().cap == 0 { // static string
                 "static (invalid)"
@@ -142,7 +171,7 @@ impl MAByteStringBuilder {
                 let cbptr = self.long().cbptr.load(Ordering::Acquire);
                 if cbptr.is_null() {
                     "unique"
-                } else if ((*cbptr).load(Ordering::Relaxed) & 1) == 0 {
+                } else if ((*cbptr).count.load(Ordering::Relaxed) & 1) == 0 {
                     "cbowned (inavlid)"
                 } else {
                     "cbinline (invalid)"
@@ -151,6 +180,44 @@ impl MAByteStringBuilder {
         }
     }
 
+    /// Return the current mode of the MAByteStringBuilder, this is always
+    /// Short or Unique.
+    pub fn mode(&self) -> Mode {
+        unsafe {
+            let len = self.long().len;
+            if len > isize::MAX as usize {  //inline string
+                Mode::Short
+            } else {
+                Mode::Unique
+            }
+        }
+    }
+
+    /// Returns the number of strings sharing the buffer, builders never
+    /// share their buffer so this is always 1.
+    pub fn strong_count(&self) -> usize {
+        1
+    }
+
+    /// Returns true if the string can be modified without copying it,
+    /// builders never share their buffer so this is always true.
+    pub fn is_unique(&self) -> bool {
+        true
+    }
+
+    /// Returns the string data if this is a static string, builders never
+    /// hold static strings so this is always None.
+    pub fn as_static(&self) -> Option<&'static [u8]> {
+        None
+    }
+
+    /// Returns true if the two strings point to the same data, since
+    /// builders never share their buffer this is only true if this and
+    /// other are the same builder.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        this.as_ptr() == other.as_ptr() && this.len() == other.len()
+    }
+
     /// ensure there is capacity for at least extracap more bytes
     /// beyond the current length of the string
     /// and return the pointer and len, and the flag that indicates
@@ -158,24 +225,42 @@ impl MAByteStringBuilder {
     /// work in the functions that need to reserve space and
     /// then use it.
     pub (super) fn reserve_extra_internal(&mut self, extracap: usize) -> (*mut u8, usize, bool) {
+        infallible(self.reserve_extra_general(extracap, false))
+    }
+
+    pub (super) fn try_reserve_extra_internal(&mut self, extracap: usize) -> Result<(*mut u8, usize, bool), TryReserveError> {
+        self.reserve_extra_general(extracap, true)
+    }
+
+    fn reserve_extra_general(&mut self, extracap: usize, fallible: bool) -> Result<(*mut u8, usize, bool), TryReserveError> {
         unsafe {
             let mut len = self.long().len;
             let mincap;
-            if len > isize::max as usize {  //inline string
+            if len > isize::MAX as usize {  //inline string
                 len = (len >> ((size_of::<usize>() - 1) * 8)) - 0x80;
-                mincap = len + extracap;
+                // saturate so that an excessive capacity is reported by reserve.
+                mincap = len.saturating_add(extracap);
                 if mincap > SHORTLEN {
                     let mincap = max(mincap,SHORTLEN*2);
-                    *self = Self::from_long(InnerLong::from_slice(slice::from_raw_parts(self.short().data.as_ptr(),len),false,mincap))
+                    let data = slice::from_raw_parts(self.short().data.as_ptr(),len);
+                    *self = Self::from_long(if fallible {
+                        InnerLong::try_from_slice(data,false,mincap)?
+                    } else {
+                        InnerLong::from_slice(data,false,mincap)
+                    });
                 } else {
-                    return (self.short_mut().data.as_mut_ptr(),len, true);
+                    return Ok((self.short_mut().data.as_mut_ptr(),len, true));
                 }
             } else {
-                mincap = len + extracap;
-                self.long_mut().reserve(mincap,false);
+                mincap = len.saturating_add(extracap);
+                if fallible {
+                    self.long_mut().try_reserve(mincap,false)?;
+                } else {
+                    self.long_mut().reserve(mincap,false);
+                }
             }
             // if we reach here, we know it's a "long" String.
-            return (self.long().ptr, len, false);
+            return Ok((self.long().ptr, len, false));
         }
     }
 
@@ -183,7 +268,7 @@ impl MAByteStringBuilder {
     pub fn reserve(&mut self, mincap: usize) {
         unsafe {
             let mut len = self.long().len;
-            if len > isize::max as usize {  //inline string
+            if len > isize::MAX as usize {  //inline string
                 if mincap > SHORTLEN {
                     len = (len >> ((size_of::<usize>() - 1) * 8)) - 0x80;
                     let mincap = max(mincap,SHORTLEN*2);
@@ -195,11 +280,52 @@ impl MAByteStringBuilder {
         }
     }
 
+    /// Like reserve, but returns an error rather than aborting if memory
+    /// allocation fails.
+    pub fn try_reserve(&mut self, mincap: usize) -> Result<(), TryReserveError> {
+        unsafe {
+            let mut len = self.long().len;
+            if len > isize::MAX as usize {  //inline string
+                if mincap > SHORTLEN {
+                    len = (len >> ((size_of::<usize>() - 1) * 8)) - 0x80;
+                    let mincap = max(mincap,SHORTLEN*2);
+                    *self = Self::from_long(InnerLong::try_from_slice(slice::from_raw_parts(self.short().data.as_ptr(),len),false,mincap)?)
+                }
+            } else {
+                self.long_mut().try_reserve(mincap,false)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Shrinks the capacity of the string as much as possible, while keeping
+    /// it at least mincap. If the string fits in a short string it is
+    /// converted to one, otherwise the buffer is reallocated if it is larger
+    /// than needed for mincap bytes and an inline control block.
+    pub fn shrink_to(&mut self, mincap: usize) {
+        unsafe {
+            let len = self.long().len;
+            if len > isize::MAX as usize { return } //inline string
+            if len <= SHORTLEN && mincap <= SHORTLEN {
+                let short = Self::from_slice(self);
+                *self = short;
+            } else {
+                self.long_mut().shrink_to(mincap, false);
+            }
+        }
+    }
+
+    /// Shrinks the capacity of the string as much as possible, see
+    /// shrink_to.
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink_to(0);
+    }
+
     /// report the "capacity" of the string.
     pub fn capacity(&self) -> usize {
         unsafe {
             let len = self.long().len;
-            if len > isize::max as usize {  //inline string
+            if len > isize::MAX as usize {  //inline string
                 SHORTLEN
             } else {
                 self.long().cap
@@ -207,11 +333,36 @@ impl MAByteStringBuilder {
         }
     }
 
+    /// Returns the number of bytes of heap memory used by the string, short
+    /// strings return 0.
+    pub fn heap_size(&self) -> usize {
+        unsafe {
+            let len = self.long().len;
+            if len > isize::MAX as usize { return 0 } //inline string
+            self.long().cap
+        }
+    }
+
+    /// Returns a snapshot of the internal representation of the string, for
+    /// debugging.
+    pub fn layout(&self) -> Layout {
+        unsafe { layout::layout(self.long()) }
+    }
+
+    /// Checks that the internal representation of the string is valid, and
+    /// panics with a description of the problem and the layout if it is
+    /// not. Builders may only be short or uniquely owned. This is only
+    /// available in debug builds, or with the check-invariants feature.
+    #[cfg(any(debug_assertions, feature = "check-invariants"))]
+    pub fn check_invariants(&self) {
+        unsafe { layout::check_invariants(self.long(), true) }
+    }
+
     // clears the string, retaining it's capacity.
     pub fn clear(&mut self) {
         unsafe {
             let len = self.long().len;
-            if len > isize::max as usize {  //inline string
+            if len > isize::MAX as usize {  //inline string
                 self.short_mut().len = 0x80;
             } else {
                 self.long_mut().len = 0;
@@ -220,10 +371,11 @@ impl MAByteStringBuilder {
     }
 
     /// convert the MAByteStringBuilder into a Vec, this may allocate.
+    #[cfg(feature = "alloc")]
     pub fn into_vec(self) -> Vec<u8> {
         unsafe {
             let mut len = self.long().len;
-            if len > isize::max as usize {  //inline string
+            if len > isize::MAX as usize {  //inline string
                 len = (len >> ((size_of::<usize>() - 1) * 8)) - 0x80;
                 return slice::from_raw_parts(self.short().data.as_ptr(), len).to_vec();
             }
@@ -239,6 +391,40 @@ impl MAByteStringBuilder {
         *self += bytestringbuilder;
     }
 
+    /// Like push_slice, but returns an error rather than aborting if
+    /// memory allocation fails.
+    pub fn try_push_slice(&mut self, bytestringbuilder: &[u8]) -> Result<(), TryReserveError> {
+        unsafe {
+            let (_, len, short) = self.try_reserve_extra_internal(bytestringbuilder.len())?;
+            self.append_internal(len, short, bytestringbuilder);
+        }
+        Ok(())
+    }
+
+    // copy other to the end of the string, after reserving space for it.
+    // The pointer is fetched again here, as the one returned when reserving
+    // may point into a short string, and reborrowing self for this call
+    // invalidates it.
+    // SAFETY: len and short must be the result of reserving space for other.
+    unsafe fn append_internal(&mut self, mut len: usize, short: bool, other: &[u8]) {
+        unsafe {
+            let ptr = if short { self.short_mut().data.as_mut_ptr() } else { self.long().ptr };
+            ptr::copy_nonoverlapping(other.as_ptr(), ptr.add(len), other.len());
+            len += other.len();
+            if short {
+                self.short_mut().len = (len + 0x80) as u8;
+            } else {
+                self.long_mut().len = len;
+            }
+        }
+    }
+
+    /// Like clone, but returns an error rather than aborting if memory
+    /// allocation fails.
+    pub fn try_clone(&self) -> Result<Self, TryReserveError> {
+        Self::try_from_slice(self)
+    }
+
     // Joins together an iterator of strings, using self as a seperator.
     pub fn join<T,I>(&self, iter : I) -> Self
     where
@@ -253,10 +439,10 @@ impl Drop for MAByteStringBuilder {
     fn drop(&mut self) {
         unsafe {
             let len = self.long().len;
-            if len > isize::max as usize { return }; //inline string
+            if len > isize::MAX as usize { return }; //inline string
             let cap = self.long().cap;
-            // we hold the only reference, turn it back into a vec so rust will free it.
-            let _ = Vec::from_raw_parts(self.long().ptr, self.long().len, cap);
+            // we hold the only reference.
+            free_buffer(self.long().ptr, self.long().len, cap);
         }
     }
 }
@@ -273,7 +459,7 @@ impl Deref for MAByteStringBuilder {
    fn deref(&self) -> &[u8] {
         unsafe {
             let mut len = self.long().len;
-            let ptr = if len > isize::max as usize {
+            let ptr = if len > isize::MAX as usize {
                 len = (len >> ((size_of::<usize>() - 1) * 8)) - 0x80;
                 self.short().data.as_ptr()
             } else {
@@ -289,7 +475,7 @@ impl DerefMut for MAByteStringBuilder {
    fn deref_mut(&mut self) -> &mut [u8] {
         unsafe {
             let mut len = self.long().len;
-            let ptr = if len > isize::max as usize {
+            let ptr = if len > isize::MAX as usize {
                 len = (len >> ((size_of::<usize>() - 1) * 8)) - 0x80;
                 self.short_mut().data.as_mut_ptr()
             } else {
@@ -311,14 +497,8 @@ impl Add<&[u8]> for MAByteStringBuilder {
 impl AddAssign<&[u8]> for MAByteStringBuilder {
     fn add_assign(&mut self, other: &[u8]) {
         unsafe {
-            let (ptr, mut len, short) = self.reserve_extra_internal(other.len());
-            ptr::copy_nonoverlapping(other.as_ptr(), ptr.add(len), other.len());
-            len += other.len();
-            if short {
-                self.short_mut().len = (len + 0x80) as u8;
-            } else {
-                self.long_mut().len = len;
-            }
+            let (_, len, short) = self.reserve_extra_internal(other.len());
+            self.append_internal(len, short, other);
         }
     }
 }
@@ -377,7 +557,7 @@ impl Hash for MAByteStringBuilder {
 impl PartialOrd for MAByteStringBuilder {
     #[inline]
     fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
-        self.deref().partial_cmp(other.deref())
+        Some(self.cmp(other))
     }
 }
 
@@ -388,7 +568,7 @@ impl Ord for MAByteStringBuilder {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "alloc"))]
 macro_rules! assert_mode {
     ($s:expr, $expectedmode:expr) => {
         for _n in 1..=1 { // loop for break, since block labels are not supported in rust 1.63
@@ -408,6 +588,7 @@ macro_rules! assert_mode {
     }
 }
 
+#[cfg(feature = "alloc")]
 #[test]
 fn test_reserve_extra_internal() {
     let mut s = MAByteStringBuilder::from_slice(b"test");
@@ -498,6 +679,7 @@ impl<const N: usize> From<&[u8;N]> for MAByteStringBuilder {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl From<Vec<u8>> for MAByteStringBuilder {
     #[inline]
     fn from(s : Vec<u8>) -> Self {
@@ -512,6 +694,7 @@ impl From<MAByteString> for MAByteStringBuilder {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl From<&Vec<u8>> for MAByteStringBuilder {
     #[inline]
     fn from(s : &Vec<u8>) -> Self {
@@ -1,11 +1,15 @@
 use crate::MAString;
 use crate::MAStringBuilder;
 use crate::CustomCow;
+#[cfg(feature = "alloc")]
 use alloc::borrow::Cow;
+#[cfg(feature = "alloc")]
 use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
 use alloc::string::String;
 use crate::MAByteString;
 use crate::MAByteStringBuilder;
+#[cfg(feature = "alloc")]
 use alloc::vec::Vec;
 use core::borrow::Borrow;
 use core::ops::Deref;
@@ -116,7 +120,7 @@ where
     loop {
         block[i] = iter.next();
         if block[i].is_some() && i < ITERBLOCKLEN-1 {
-            i = i + 1;
+            i += 1;
         } else {
             let (blocklen,end) = if block[i].is_some() {
                 (i + 1, false)
@@ -172,8 +176,11 @@ macro_rules! impl_fromiter_stringlike {
 impl_fromiter_bytelike!(MAByteString,MAByteStringBuilder,u8);
 impl_fromiter_bytelike!(MAByteString,MAByteStringBuilder,&'a u8);
 impl_fromiter_stringlike!(MAByteString,MAByteStringBuilder,&'a [u8]);
+#[cfg(feature = "alloc")]
 impl_fromiter_stringlike!(MAByteString,MAByteStringBuilder,Vec<u8>);
+#[cfg(feature = "alloc")]
 impl_fromiter_stringlike!(MAByteString,MAByteStringBuilder,Box<[u8]>);
+#[cfg(feature = "alloc")]
 impl_fromiter_stringlike!(MAByteString,MAByteStringBuilder,Cow<'a,[u8]>);
 impl_fromiter_stringlike!(MAByteString,MAByteStringBuilder,CustomCow<'a,MAByteString>);
 impl_fromiter_stringlike!(MAByteString,MAByteStringBuilder,CustomCow<'a,MAByteStringBuilder>);
@@ -183,8 +190,11 @@ impl_fromiter_stringlike!(MAByteString,MAByteStringBuilder,MAByteStringBuilder);
 impl_fromiter_charlike!(MAString,MAStringBuilder,char);
 impl_fromiter_charlike!(MAString,MAStringBuilder,&'a char);
 impl_fromiter_stringlike!(MAString,MAStringBuilder,&'a str);
+#[cfg(feature = "alloc")]
 impl_fromiter_stringlike!(MAString,MAStringBuilder,String);
+#[cfg(feature = "alloc")]
 impl_fromiter_stringlike!(MAString,MAStringBuilder,Box<str>);
+#[cfg(feature = "alloc")]
 impl_fromiter_stringlike!(MAString,MAStringBuilder,Cow<'a,str>);
 impl_fromiter_stringlike!(MAString,MAStringBuilder,CustomCow<'a,MAString>);
 impl_fromiter_stringlike!(MAString,MAStringBuilder,CustomCow<'a,MAStringBuilder>);
@@ -194,8 +204,11 @@ impl_fromiter_stringlike!(MAString,MAStringBuilder,MAStringBuilder);
 impl_fromiter_bytelike!(MAByteStringBuilder,MAByteStringBuilder,u8);
 impl_fromiter_bytelike!(MAByteStringBuilder,MAByteStringBuilder,&'a u8);
 impl_fromiter_stringlike!(MAByteStringBuilder,MAByteStringBuilder,&'a [u8]);
+#[cfg(feature = "alloc")]
 impl_fromiter_stringlike!(MAByteStringBuilder,MAByteStringBuilder,Vec<u8>);
+#[cfg(feature = "alloc")]
 impl_fromiter_stringlike!(MAByteStringBuilder,MAByteStringBuilder,Box<[u8]>);
+#[cfg(feature = "alloc")]
 impl_fromiter_stringlike!(MAByteStringBuilder,MAByteStringBuilder,Cow<'a,[u8]>);
 impl_fromiter_stringlike!(MAByteStringBuilder,MAByteStringBuilder,CustomCow<'a,MAByteString>);
 impl_fromiter_stringlike!(MAByteStringBuilder,MAByteStringBuilder,CustomCow<'a,MAByteStringBuilder>);
@@ -205,8 +218,11 @@ impl_fromiter_stringlike!(MAByteStringBuilder,MAByteStringBuilder,MAByteStringBu
 impl_fromiter_charlike!(MAStringBuilder,MAStringBuilder,char);
 impl_fromiter_charlike!(MAStringBuilder,MAStringBuilder,&'a char);
 impl_fromiter_stringlike!(MAStringBuilder,MAStringBuilder,&'a str);
+#[cfg(feature = "alloc")]
 impl_fromiter_stringlike!(MAStringBuilder,MAStringBuilder,String);
+#[cfg(feature = "alloc")]
 impl_fromiter_stringlike!(MAStringBuilder,MAStringBuilder,Box<str>);
+#[cfg(feature = "alloc")]
 impl_fromiter_stringlike!(MAStringBuilder,MAStringBuilder,Cow<'a,str>);
 impl_fromiter_stringlike!(MAStringBuilder,MAStringBuilder,CustomCow<'a,MAString>);
 impl_fromiter_stringlike!(MAStringBuilder,MAStringBuilder,CustomCow<'a,MAStringBuilder>);
@@ -1,38 +1,106 @@
-use core::sync::atomic::AtomicPtr;
-use core::sync::atomic::AtomicUsize;
 use core::sync::atomic::Ordering;
-use core::sync::atomic::fence;
+use crate::sync::AtomicPtr;
+use crate::sync::AtomicUsize;
+use crate::sync::fence;
+use crate::sync::spin_loop;
 use core::mem::size_of;
+#[cfg(feature = "alloc")]
 use core::mem;
 use core::mem::align_of;
 use core::ptr;
 use core::slice;
 use core::cmp::max;
-use core::cmp::min;
 use crate::limitedusize::LimitedU8;
 use crate::limitedusize::LimitedUSize;
+use crate::allocstats;
+use crate::allocstats::Event;
 
-extern crate alloc;
+#[cfg(feature = "alloc")]
 use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
 use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
+use crate::TryReserveError;
 
 //contol block values
-//stores reference count * 2
+//count stores reference count * 2
 //even numbers for owned
 //odd numbers for inline
+//weak stores the number of weak references, plus one which is held
+//collectively by the strong references. The buffer is freed when it
+//reaches zero. usize::MAX means it is locked by is_unique.
+//cap stores the capacity of the allocation the control block belongs to,
+//this is needed to free the buffer when the last owner only references
+//part of it.
+#[repr(C)]
+pub (super) struct ControlBlock {
+    pub (super) count: AtomicUsize,
+    pub (super) weak: AtomicUsize,
+    pub (super) cap: usize,
+}
+
+impl ControlBlock {
+    #[inline]
+    pub (super) fn new(count: usize, cap: usize) -> Self {
+        ControlBlock { count: AtomicUsize::new(count), weak: AtomicUsize::new(1), cap: cap }
+    }
+
+    // allocate a seperately owned control block.
+    #[cfg(feature = "alloc")]
+    fn new_boxed(count: usize, cap: usize, fallible: bool) -> Result<*mut ControlBlock, TryReserveError> {
+        if !fallible { return Ok(Box::into_raw(Box::new(ControlBlock::new(count, cap)))) }
+        // Box::try_new is unstable, but a boxed slice of one element has
+        // the same layout as a Box.
+        let mut v = Vec::new();
+        v.try_reserve_exact(1)?;
+        v.push(ControlBlock::new(count, cap));
+        Ok(Box::into_raw(v.into_boxed_slice()) as *mut ControlBlock)
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn new_boxed(_count: usize, _cap: usize, fallible: bool) -> Result<*mut ControlBlock, TryReserveError> {
+        no_alloc(fallible)
+    }
+
+    // the heap memory used by a shared buffer, including the control block.
+    pub (super) fn heap_size(&self) -> usize {
+        if (self.count.load(Ordering::Relaxed) & 1) == 0 { //owned control block
+            self.cap + size_of::<ControlBlock>()
+        } else {
+            self.cap
+        }
+    }
 
+    // check whether the caller holds the only strong reference and there
+    // are no weak references. As in Arc, the weak count is locked while
+    // checking the strong count, so that another owner cannot create a weak
+    // reference and drop their strong reference while we are checking.
+    #[inline]
+    pub (super) fn is_unique(&self) -> bool {
+        if self.weak.compare_exchange(1, usize::MAX, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            return false;
+        }
+        let unique = (self.count.load(Ordering::Acquire) >> 1) == 1;
+        self.weak.store(1, Ordering::Release);
+        unique
+    }
+}
+
+//In the shared ownership modes, ptr + cap is always the end of the
+//allocation, even if ptr does not point to the start of it.
 #[repr(C)]
 pub (super) struct InnerLong {
     #[cfg(target_endian="big")]
     pub (super) len: usize,
     pub (super) cap: usize,
     pub (super) ptr: * mut u8,
-    pub (super) cbptr: AtomicPtr<AtomicUsize>,
+    pub (super) cbptr: AtomicPtr<ControlBlock>,
     #[cfg(target_endian="little")]
     pub (super) len: usize,
 }
 
 impl InnerLong {
+    #[cfg(feature = "alloc")]
     #[inline]
     pub (super) fn from_vec(mut v: Vec<u8>, allowcb: bool, mincap: usize) -> Self {
         // it would be nice to use into_raw_parts here, but it's unstable.
@@ -42,24 +110,12 @@ impl InnerLong {
         let mincap = max(len,mincap);
         let ptr = v.as_mut_ptr();
         mem::forget(v);
-        let mut cbptr : * mut AtomicUsize = ptr::null_mut();
+        let mut cbptr : * mut ControlBlock = ptr::null_mut();
         if allowcb {
-            //println!("checking if we have room for a control block");
             unsafe {
-                //check if we have room for a control block.
-                //math wont overflow because a vec is limited to isize,
-                //which has half the range of usize.
-                let end = ptr.add(mincap);
-                let mut cbstart = mincap + end.align_offset(align_of::<AtomicUsize>());
-                let cbrequired = cbstart + size_of::<AtomicUsize>();
-                if cbrequired <= cap {
-                    let cbextraspace = (cap - cbrequired) & !(align_of::<AtomicUsize>()-1);
-                    cbstart += cbextraspace;
-                    //println!("allocating control block");
-                    cbptr = ptr.add(cbstart) as * mut AtomicUsize;
-                    *cbptr = AtomicUsize::new(3);
-                } else {
-                    //println!("no room for control block!");
+                if let Some(cbstart) = inline_cb_offset(ptr, mincap, cap) {
+                    cbptr = ptr.add(cbstart) as * mut ControlBlock;
+                    ptr::write(cbptr, ControlBlock::new(3, cap));
                 }
             }
         }
@@ -68,13 +124,35 @@ impl InnerLong {
 
     #[inline]
     pub (super) fn from_slice(s: &[u8], allowcb: bool, mincap: usize) -> Self {
-        let len = max(s.len(),mincap);
-        let mask = align_of::<AtomicUsize>() - 1;
-        let veccap = ((len + mask) & !mask) + size_of::<AtomicUsize>();
-        //println!("len:{len} allowcb:{allowcb} veccap:{veccap}");
-        let mut v = Vec::with_capacity(veccap);
+        infallible(Self::from_slice_internal(s, allowcb, mincap, false))
+    }
+
+    #[inline]
+    pub (super) fn try_from_slice(s: &[u8], allowcb: bool, mincap: usize) -> Result<Self, TryReserveError> {
+        Self::from_slice_internal(s, allowcb, mincap, true)
+    }
+
+    // if fallible is false, allocation failures abort and capacity overflows
+    // panic as they do for Vec, so an error is never returned.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    fn from_slice_internal(s: &[u8], allowcb: bool, mincap: usize, fallible: bool) -> Result<Self, TryReserveError> {
+        let veccap = alloc_size(max(s.len(),mincap));
+        //println!("allowcb:{allowcb} veccap:{veccap}");
+        let mut v = Vec::new();
+        if fallible {
+            v.try_reserve_exact(veccap)?;
+        } else {
+            v.reserve_exact(veccap);
+        }
+        allocstats::count(Event::Buffer);
         v.extend_from_slice(s);
-        Self::from_vec(v,allowcb,mincap)
+        Ok(Self::from_vec(v,allowcb,mincap))
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn from_slice_internal(_s: &[u8], _allowcb: bool, _mincap: usize, fallible: bool) -> Result<Self, TryReserveError> {
+        no_alloc(fallible)
     }
 
     // ensure the pointer is unique
@@ -83,49 +161,185 @@ impl InnerLong {
     // then it's capacity is left unchanged.
     #[inline]
     pub (super) fn make_unique(&mut self, mincap: usize, allowcb: bool) {
+        infallible(self.make_unique_internal(mincap, allowcb, false))
+    }
+
+    #[inline]
+    pub (super) fn try_make_unique(&mut self, mincap: usize, allowcb: bool) -> Result<(), TryReserveError> {
+        self.make_unique_internal(mincap, allowcb, true)
+    }
+
+    #[inline]
+    fn make_unique_internal(&mut self, mincap: usize, allowcb: bool, fallible: bool) -> Result<(), TryReserveError> {
         //println!("in make_unique mincap={mincap} allowcb={allowcb}");
         if self.cap == 0 { // static string, we need to copy
             unsafe {
-                *self = InnerLong::from_slice(slice::from_raw_parts(self.ptr,self.len),allowcb,mincap);
+                *self = InnerLong::from_slice_internal(slice::from_raw_parts(self.ptr,self.len),allowcb,mincap,fallible)?;
             }
+            allocstats::count(Event::StaticPromotion);
         } else {
             let cbptr = self.cbptr.load(Ordering::Relaxed);
             if cbptr.is_null() {
                 // we already have unique ownership of the string.
             } else {
                 unsafe {
-                    let refcount = (*cbptr).load(Ordering::Relaxed) >> 1;
-                    if refcount == 1 {
+                    if (*cbptr).is_unique() {
                         // we are the only owner of the String
                         if !allowcb {
                             // switch it from shared ownership mode to unique ownership mode.
-                            if ((*cbptr).load(Ordering::Relaxed) & 1) == 0 {
-                                //free control block pointer
-                                let _ = Box::from_raw(cbptr);
-                            }
-                            self.cbptr.store(ptr::null_mut(),Ordering::Relaxed);
+                            self.unshare();
                         }
                     } else {
                         // there are other owners, we need to copy
-                        *self = InnerLong::from_slice(slice::from_raw_parts(self.ptr,self.len),allowcb,mincap) ;
+                        *self = InnerLong::from_slice_internal(slice::from_raw_parts(self.ptr,self.len),allowcb,mincap,fallible)?;
+                        allocstats::count(Event::CowCopy);
                     }
                 }
             }
         }
+        Ok(())
+    }
+
+    // switch a string from shared ownership mode to unique ownership mode,
+    // if the string only references part of it's buffer then the data is
+    // moved to the start of the buffer.
+    // SAFETY: callers must ensure that the string is in shared ownership
+    // mode and that we are the only owner, with no weak references.
+    unsafe fn unshare(&mut self) {
+        let cbptr = self.cbptr.load(Ordering::Relaxed);
+        unsafe {
+            let alloccap = (*cbptr).cap;
+            if ((*cbptr).count.load(Ordering::Relaxed) & 1) == 0 {
+                //free control block pointer
+                free_cb(cbptr);
+            }
+            if alloccap != self.cap {
+                let base = self.ptr.add(self.cap).sub(alloccap);
+                ptr::copy(self.ptr, base, self.len);
+                self.ptr = base;
+                self.cap = alloccap;
+            }
+        }
+        self.cbptr.store(ptr::null_mut(),Ordering::Relaxed);
+    }
+
+    // return the control block of a long string that is not static,
+    // switching it to shared ownership mode if needed. This takes &self
+    // because it is used by Clone, so the control block is installed with
+    // a compare exchange in case another thread is doing the same.
+    // If there is room after the data, an inline control block is used
+    // rather than allocating one.
+    pub (super) fn get_or_create_cb(&self) -> *mut ControlBlock {
+        infallible(self.get_or_create_cb_internal(false))
+    }
+
+    pub (super) fn try_get_or_create_cb(&self) -> Result<*mut ControlBlock, TryReserveError> {
+        self.get_or_create_cb_internal(true)
+    }
+
+    fn get_or_create_cb_internal(&self, fallible: bool) -> Result<*mut ControlBlock, TryReserveError> {
+        let cbptr = self.load_cb();
+        if !cbptr.is_null() { return Ok(cbptr) }
+        // we are in unique ownership mode, so the pointer and
+        // capacity describe the whole allocation.
+        if let Some(cbstart) = unsafe { inline_cb_offset(self.ptr, self.len, self.cap) } {
+            // other threads may be reading the string, so claim the space
+            // before writing the control block into it.
+            if self.cbptr.compare_exchange(ptr::null_mut(),CB_INSTALLING,Ordering::Acquire,Ordering::Relaxed).is_err() {
+                return Ok(self.load_cb());
+            }
+            unsafe {
+                let newcbptr = self.ptr.add(cbstart) as *mut ControlBlock;
+                ptr::write(newcbptr, ControlBlock::new(3, self.cap));
+                self.cbptr.store(newcbptr, Ordering::Release);
+                return Ok(newcbptr);
+            }
+        }
+        let newcbptr = ControlBlock::new_boxed(2, self.cap, fallible)?;
+        if self.cbptr.compare_exchange(ptr::null_mut(),newcbptr,Ordering::AcqRel,Ordering::Acquire).is_err() {
+            unsafe { free_cb(newcbptr); }
+            Ok(self.load_cb())
+        } else {
+            allocstats::count(Event::BoxedCb);
+            Ok(newcbptr)
+        }
+    }
+
+    // load the control block pointer of a string that may be shared with
+    // other threads, waiting if another thread is installing an inline
+    // control block.
+    #[inline]
+    pub (super) fn load_cb(&self) -> *mut ControlBlock {
+        loop {
+            let cbptr = self.cbptr.load(Ordering::Acquire);
+            if cbptr != CB_INSTALLING { return cbptr }
+            spin_loop();
+        }
+    }
+
+    // check whether ptr is the start of the allocation, which is always the
+    // case without a control block. Strings created by the slice methods may
+    // point into the middle of their buffer.
+    pub (super) fn at_alloc_start(&self) -> bool {
+        let cbptr = self.load_cb();
+        if cbptr.is_null() { return true }
+        return unsafe { (*cbptr).cap == self.cap };
+    }
+
+    // check whether the string uses all of it's allocation, apart from the
+    // spare capacity from_slice would have given it. Static strings don't
+    // have an allocation, so they always do.
+    #[cfg(feature = "alloc")]
+    pub (super) fn is_tight(&self) -> bool {
+        if self.cap == 0 { return true }
+        return self.at_alloc_start() && self.cap <= alloc_size(self.len);
+    }
+
+    // put a uniquely owned string into shared ownership mode with an inline
+    // control block, reallocating it if there is no room for one after
+    // mincap bytes.
+    pub (super) fn make_shared(&mut self, mincap: usize) {
+        if self.cap == 0 || !self.cbptr.load(Ordering::Relaxed).is_null() { return }
+        let mincap = max(mincap, self.len);
+        if unsafe { inline_cb_offset(self.ptr, mincap, self.cap) }.is_some() {
+            self.get_or_create_cb();
+        } else {
+            unsafe {
+                *self = InnerLong::from_slice(slice::from_raw_parts(self.ptr, self.len), true, mincap);
+            }
+        }
+    }
+
+    // reallocate the buffer if it is larger than from_slice would allocate
+    // for mincap bytes.
+    // SAFETY: callers must ensure that the innerlong has unique ownership.
+    pub (super) unsafe fn shrink_to(&mut self, mincap: usize, allowcb: bool) {
+        let mincap = max(mincap, self.len);
+        let cbptr = self.cbptr.load(Ordering::Relaxed);
+        // a string created by slice may only reference part of it's buffer.
+        let alloccap = if cbptr.is_null() { self.cap } else { unsafe { (*cbptr).cap } };
+        if alloccap <= alloc_size(mincap) { return }
+        unsafe {
+            *self = Self::from_slice(slice::from_raw_parts(self.ptr, self.len), allowcb, mincap);
+        }
     }
 
     pub (super) fn usablecap(&self) -> usize {
-        // check for an inline control block.
-        let ptr = self.ptr as usize;
+        if self.cap == 0 { return 0 } //static string
+        // check for an inline control block, comparing the pointers rather
+        // than their addresses so that provenance is not lost.
         // use relaxed as the only time the value critically matters is
         // when we already have unique ownership.
-        let cbptr = self.cbptr.load(Ordering::Relaxed) as usize;
-        if cbptr < ptr {
-            // no control block, or outline control block located before data
+        let cbptr = self.cbptr.load(Ordering::Relaxed) as *const u8;
+        let ptr = self.ptr as *const u8;
+        // ptr + cap is the end of the allocation, so computing it is fine.
+        if cbptr < ptr || cbptr >= ptr.wrapping_add(self.cap) {
+            // no control block, or outline control block
             self.cap
         } else {
-            // inline control block, or outline control block located after data.
-            min(cbptr - ptr,self.cap)
+            // an inline control block is in the same allocation as the data,
+            // so offset_from can be used.
+            unsafe { cbptr.offset_from(ptr) as usize }
         }
     }
 
@@ -133,18 +347,32 @@ impl InnerLong {
     // implement an exponential reallocation
     // SAFETY: callers must ensure that the innerlong has unique ownership
     // before calling, use make_unique if needed.
-    pub (super) unsafe fn reserve(&mut self, mut mincap: usize, allowcb: bool) {
+    pub (super) unsafe fn reserve(&mut self, mincap: usize, allowcb: bool) {
+        infallible(unsafe { self.reserve_internal(mincap, allowcb, false) })
+    }
+
+    // SAFETY: as for reserve
+    pub (super) unsafe fn try_reserve(&mut self, mincap: usize, allowcb: bool) -> Result<(), TryReserveError> {
+        unsafe { self.reserve_internal(mincap, allowcb, true) }
+    }
+
+    unsafe fn reserve_internal(&mut self, mut mincap: usize, allowcb: bool, fallible: bool) -> Result<(), TryReserveError> {
         let cap = self.cap;
         if mincap <= cap { 
-            if mincap <= self.usablecap() { return }
+            if mincap <= self.usablecap() { return Ok(()) }
             // an inline control block is reducing our usable capacity, get rid of it
-            self.cbptr = AtomicPtr::new(ptr::null_mut());
-            return
+            unsafe { self.unshare(); }
+            return Ok(())
         };
-        mincap = max(mincap, cap * 2);
+        // double the capacity, not counting the room alloc_size leaves for a
+        // control block, otherwise that would be doubled again on every
+        // reallocation.
+        mincap = max(mincap, cap.saturating_sub(size_of::<ControlBlock>()) * 2);
         unsafe {
-            *self = Self::from_slice(slice::from_raw_parts(self.ptr, self.len), allowcb,  mincap);
+            *self = Self::from_slice_internal(slice::from_raw_parts(self.ptr, self.len), allowcb, mincap, fallible)?;
         }
+        allocstats::count(Event::Reallocation);
+        Ok(())
     }
 
 }
@@ -157,18 +385,173 @@ impl Drop for InnerLong {
         let cbptr = self.cbptr.load(Ordering::Relaxed);
         unsafe {
             if !cbptr.is_null() {
-                let oldcb = (*cbptr).fetch_sub(2, Ordering::Release); //decrease the refcount
+                let oldcb = (*cbptr).count.fetch_sub(2, Ordering::Release); //decrease the refcount
                 if oldcb > 3 { return } // there are still other references
                 fence(Ordering::Acquire);
-                if (oldcb & 1) == 0 { //owned control block
-                    let _ = Box::from_raw(cbptr);
-                }
+                // release the weak reference held by the strong references.
+                free_shared(self.ptr, cap, cbptr);
+                return
             }
-            // we hold the only reference, turn it back into a vec so rust will free it.
-            let _ = Vec::from_raw_parts(self.ptr, len, cap);
+            // we hold the only reference.
+            free_buffer(self.ptr, len, cap);
+        }
+    }
+
+}
+
+// unwrap the result of an allocating function called with fallible set to
+// false, which never returns an error.
+#[inline]
+pub (super) fn infallible<T>(r: Result<T, TryReserveError>) -> T {
+    match r {
+        Ok(v) => v,
+        Err(_) => unreachable!(),
+    }
+}
+
+// what to do when taking a new reference would take a reference count
+// above usize::MAX / 2.
+pub (super) enum Overflow {
+    // return a reference to the buffer in static mode without counting it.
+    // The count can never get back down to zero, since there can't be that
+    // many references in memory, so the buffer will never be freed.
+    Leak,
+    // return a copy of the data.
+    Copy,
+}
+
+// the policy is chosen with the refcount-overflow-* features, if more than
+// one is enabled abort takes priority over leak, and leak over copy. With
+// none of them enabled we panic.
+#[cold]
+pub (super) fn refcount_overflow() -> Overflow {
+    if cfg!(feature = "refcount-overflow-abort") {
+        abort();
+    } else if cfg!(feature = "refcount-overflow-leak") {
+        return Overflow::Leak;
+    } else if cfg!(feature = "refcount-overflow-copy") {
+        return Overflow::Copy;
+    }
+    panic!("reference count too high, you have a refrence leak");
+}
+
+// core has no stable way to abort, but panicking while already panicking
+// aborts the process.
+#[cold]
+fn abort() -> ! {
+    struct Abort;
+    impl Drop for Abort {
+        fn drop(&mut self) {
+            panic!("aborting");
         }
     }
+    let _abort = Abort;
+    panic!("reference count too high, you have a refrence leak");
+}
+
+// release a weak reference to a shared buffer, freeing the buffer and the
+// control block if it was the last one.
+// SAFETY: ptr and cap must describe a string in shared ownership mode using
+// cbptr, whose reference is being given up.
+pub (super) unsafe fn free_shared(ptr: *mut u8, cap: usize, cbptr: *mut ControlBlock) {
+    unsafe {
+        if (*cbptr).weak.fetch_sub(1, Ordering::Release) != 1 { return }
+        fence(Ordering::Acquire);
+        // we may only reference part of the buffer, so find the start
+        // and size of the allocation before freeing the control block.
+        let alloccap = (*cbptr).cap;
+        let base = ptr.add(cap).sub(alloccap);
+        // there are no strong references left, so only the inline flag remains.
+        if ((*cbptr).count.load(Ordering::Relaxed) & 1) == 0 { //owned control block
+            free_cb(cbptr);
+        }
+        free_buffer(base, 0, alloccap);
+    }
+}
+
+// free a buffer by turning it back into a vec so rust will free it.
+// SAFETY: ptr and cap must describe an allocation made by a Vec<u8> which
+// has not been freed, and is not referenced by any other string.
+#[cfg(feature = "alloc")]
+#[inline]
+pub (super) unsafe fn free_buffer(ptr: *mut u8, len: usize, cap: usize) {
+    unsafe { drop(Vec::from_raw_parts(ptr, len, cap)); }
+}
+
+// free a seperately owned control block.
+// SAFETY: cbptr must have come from ControlBlock::new_boxed, and must not
+// be used afterwards.
+#[cfg(feature = "alloc")]
+#[inline]
+unsafe fn free_cb(cbptr: *mut ControlBlock) {
+    unsafe { drop(Box::from_raw(cbptr)); }
+}
+
+// without alloc, strings are only ever short or static, so there is nothing
+// to free.
+#[cfg(not(feature = "alloc"))]
+pub (super) unsafe fn free_buffer(_ptr: *mut u8, _len: usize, _cap: usize) {
+    unreachable!();
+}
+
+#[cfg(not(feature = "alloc"))]
+unsafe fn free_cb(_cbptr: *mut ControlBlock) {
+    unreachable!();
+}
+
+// without alloc, any string that doesn't fit in a short string is an
+// allocation failure.
+#[cfg(not(feature = "alloc"))]
+#[cold]
+fn no_alloc<T>(fallible: bool) -> Result<T, TryReserveError> {
+    if !fallible {
+        panic!("string too long to store without the alloc feature");
+    }
+    Err(TryReserveError { _private: () })
+}
+
+/// The error type for the try_* methods, returned when a string no longer
+/// fits in a short string. Without the alloc feature the string types can't
+/// allocate memory, so alloc's TryReserveError isn't available.
+#[cfg(not(feature = "alloc"))]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TryReserveError {
+    _private: (),
+}
+
+#[cfg(not(feature = "alloc"))]
+impl core::fmt::Display for TryReserveError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("memory allocation failed because the alloc feature is disabled")
+    }
+}
+
+// the size of the buffer allocated for a long string with capacity cap,
+// this leaves room for an inline control block.
+#[inline]
+fn alloc_size(cap: usize) -> usize {
+    let mask = align_of::<ControlBlock>() - 1;
+    // saturate so that an excessive capacity is reported by reserve.
+    (cap.saturating_add(mask) & !mask).saturating_add(size_of::<ControlBlock>())
+}
+
+// stored in cbptr while get_or_create_cb is writing an inline control block.
+pub (super) const CB_INSTALLING: *mut ControlBlock = ptr::NonNull::dangling().as_ptr();
 
+// find room for an inline control block after len bytes of data in a
+// buffer of cap bytes, the control block is placed as far towards the end
+// of the buffer as possible. Returns the offset of the control block.
+// SAFETY: ptr must point to the start of an allocation of cap bytes.
+unsafe fn inline_cb_offset(ptr: *mut u8, len: usize, cap: usize) -> Option<usize> {
+    let end = unsafe { ptr.add(len) };
+    // align_offset may fail, for example under miri with
+    // -Zmiri-symbolic-alignment-check, as a Vec<u8> is only guaranteed to
+    // be byte aligned. The string then just doesn't get a control block.
+    let cbstart = len.checked_add(end.align_offset(align_of::<ControlBlock>()))?;
+    let cbrequired = cbstart.checked_add(size_of::<ControlBlock>())?;
+    if cbrequired > cap { return None }
+    let cbextraspace = (cap - cbrequired) & !(align_of::<ControlBlock>()-1);
+    Some(cbstart + cbextraspace)
 }
 
 pub (super) const SHORTLEN : usize = size_of::<InnerLong>()-1;
@@ -194,7 +577,7 @@ pub (super) struct InnerNiche {
     _len: usize,
     _cap: usize,
     _ptr: * mut u8,
-    _cbptr: AtomicPtr<AtomicUsize>,
+    _cbptr: AtomicPtr<ControlBlock>,
     #[cfg(target_endian="little")]
     _len: LimitedUSize,
 }
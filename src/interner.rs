@@ -0,0 +1,116 @@
+use core::sync::atomic::Ordering;
+use alloc::collections::BTreeSet;
+
+use crate::MAString;
+use crate::inner::SHORTLEN;
+
+/// MAInterner deduplicates strings, so that equal strings share a single
+/// buffer and control block.
+///
+/// Strings that can be stored as short strings are returned without being
+/// stored, since they don't use a buffer. Static strings are stored without
+/// copying.
+pub struct MAInterner {
+    // BTreeSet::new is not const in rust 1.63, so the set is created on
+    // first use to allow MAInterner::new to be const.
+    set: Option<BTreeSet<MAString>>,
+}
+
+impl MAInterner {
+    /// Creates a new, empty, MAInterner.
+    /// This will not allocate
+    pub const fn new() -> Self {
+        MAInterner { set: None }
+    }
+
+    /// Returns a string equal to s, copying it into the interner if an
+    /// equal string has not already been interned.
+    pub fn intern(&mut self, s: &str) -> MAString {
+        if s.len() <= SHORTLEN { return MAString::from_slice(s) }
+        if let Some(result) = self.get(s) { return result }
+        self.insert(MAString::from_slice(s))
+    }
+
+    /// Returns a string equal to s, storing it in the interner without
+    /// copying if an equal string has not already been interned.
+    pub fn intern_static(&mut self, s: &'static str) -> MAString {
+        if s.len() <= SHORTLEN { return MAString::from_static(s) }
+        if let Some(result) = self.get(s) { return result }
+        self.insert(MAString::from_static(s))
+    }
+
+    /// Returns a string equal to s, storing s in the interner if an equal
+    /// string has not already been interned. This will allocate a control
+    /// block if s is stored and is in unique ownership mode.
+    ///
+    /// If s is a slice of a larger string, or has more spare capacity than
+    /// MAString::from_slice would give it, a copy is stored instead, so the
+    /// interner does not keep the unused memory alive.
+    pub fn intern_mas(&mut self, s: MAString) -> MAString {
+        if s.len() <= SHORTLEN { return s }
+        if let Some(result) = self.get(&s) { return result }
+        if unsafe { s.inner.long() }.is_tight() {
+            self.insert(s)
+        } else {
+            self.insert(MAString::from_slice(&s))
+        }
+    }
+
+    /// Returns the interned string equal to s, if there is one.
+    pub fn get(&self, s: &str) -> Option<MAString> {
+        self.set.as_ref()?.get(s).cloned()
+    }
+
+    /// Returns the number of strings stored in the interner.
+    pub fn len(&self) -> usize {
+        self.set.as_ref().map_or(0, BTreeSet::len)
+    }
+
+    /// Returns true if no strings are stored in the interner.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes the strings which are not referenced outside the interner.
+    /// Static strings are never removed.
+    pub fn remove_unused(&mut self) {
+        if let Some(set) = &mut self.set {
+            set.retain(|s| {
+                unsafe {
+                    let long = s.inner.long();
+                    if long.cap == 0 { return true } // static string
+                    let cbptr = long.cbptr.load(Ordering::Acquire);
+                    ((*cbptr).count.load(Ordering::Acquire) >> 1) > 1
+                }
+            });
+        }
+    }
+
+    /// Removes all strings from the interner.
+    pub fn clear(&mut self) {
+        self.set = None;
+    }
+
+    // store a string that is not already in the interner, and return a
+    // string sharing it's buffer.
+    fn insert(&mut self, s: MAString) -> MAString {
+        // cloning puts the string in shared ownership mode if needed.
+        let result = s.clone();
+        self.set.get_or_insert_with(BTreeSet::new).insert(s);
+        result
+    }
+
+    /// Returns the global interner.
+    #[cfg(feature = "global-interner")]
+    pub fn global() -> &'static std::sync::Mutex<MAInterner> {
+        static GLOBAL: std::sync::Mutex<MAInterner> = std::sync::Mutex::new(MAInterner::new());
+        &GLOBAL
+    }
+}
+
+impl Default for MAInterner {
+    #[inline]
+    fn default() -> MAInterner {
+        Self::new()
+    }
+}
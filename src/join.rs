@@ -5,9 +5,13 @@ use crate::MAStringBuilder;
 use crate::MAByteString;
 use crate::MAByteStringBuilder;
 use crate::CustomCow;
+#[cfg(feature = "alloc")]
 use alloc::borrow::Cow;
+#[cfg(feature = "alloc")]
 use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
 use alloc::string::String;
+#[cfg(feature = "alloc")]
 use alloc::vec::Vec;
 use core::borrow::Borrow;
 
@@ -65,8 +69,11 @@ macro_rules! impl_joiner_charlike {
 impl_joiner_bytelike!(u8,[u8]);
 impl_joiner_bytelike!(&u8,[u8]);
 impl_joiner_simple!(&[u8],[u8]);
+#[cfg(feature = "alloc")]
 impl_joiner_simple!(Vec<u8>,[u8]);
+#[cfg(feature = "alloc")]
 impl_joiner_simple!(Box<[u8]>,[u8]);
+#[cfg(feature = "alloc")]
 impl_joiner_simple!(Cow<'_,[u8]>,[u8]);
 impl_joiner_simple!(CustomCow<'_,MAByteString>,[u8]);
 impl_joiner_simple!(CustomCow<'_,MAByteStringBuilder>,[u8]);
@@ -76,8 +83,11 @@ impl_joiner_simple!(MAByteStringBuilder,[u8]);
 impl_joiner_charlike!(char,str);
 impl_joiner_charlike!(&char,str);
 impl_joiner_simple!(&str,str);
+#[cfg(feature = "alloc")]
 impl_joiner_simple!(String,str);
+#[cfg(feature = "alloc")]
 impl_joiner_simple!(Box<str>,str);
+#[cfg(feature = "alloc")]
 impl_joiner_simple!(Cow<'_,str>,str);
 impl_joiner_simple!(CustomCow<'_,MAString>,str);
 impl_joiner_simple!(CustomCow<'_,MAStringBuilder>,str);
@@ -103,7 +113,7 @@ where
         let mut buf = JoinableBuf([0;4]);
         block[i] = iter.next();
         if block[i].is_some() && i < ITERBLOCKLEN-1 {
-            i = i + 1;
+            i += 1;
         } else {
             let (blocklen,end) = if block[i].is_some() {
                 (i + 1, false)
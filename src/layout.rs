@@ -0,0 +1,128 @@
+use core::sync::atomic::Ordering;
+use core::ptr;
+#[cfg(any(debug_assertions, feature = "check-invariants"))]
+use core::mem::size_of;
+#[cfg(any(debug_assertions, feature = "check-invariants"))]
+use core::mem::align_of;
+use crate::inner::InnerLong;
+use crate::inner::InnerShort;
+#[cfg(any(debug_assertions, feature = "check-invariants"))]
+use crate::inner::ControlBlock;
+use crate::inner::CB_INSTALLING;
+#[cfg(any(debug_assertions, feature = "check-invariants"))]
+use crate::inner::SHORTLEN;
+
+/// A snapshot of the internal representation of a string, returned by the
+/// layout methods. This is intended for debugging and is best printed with
+/// {:#?}.
+#[derive(Clone, Debug)]
+pub enum Layout {
+    /// A short string, len_byte is the raw length byte, which has 0x80
+    /// added to the length.
+    Short { len_byte: u8, len: usize },
+    /// A static, uniquely owned or shared string. A cap of zero indicates
+    /// a static string, and a null cbptr a uniquely owned one.
+    Long {
+        ptr: *const u8,
+        len: usize,
+        cap: usize,
+        cbptr: *const u8,
+        /// The contents of the control block, this is None if there is no
+        /// control block, or another thread is installing one.
+        control_block: Option<ControlBlockLayout>,
+    },
+}
+
+/// The contents of the control block of a string in shared ownership mode.
+#[derive(Clone, Debug)]
+pub struct ControlBlockLayout {
+    /// The raw count field, the reference count * 2, plus one for an
+    /// inline control block.
+    pub count: usize,
+    /// Whether the control block is stored in the string's buffer.
+    pub inline: bool,
+    /// The reference count.
+    pub strong: usize,
+    /// The number of weak references plus one, usize::MAX while the count
+    /// is locked by is_unique.
+    pub weak: usize,
+    /// The capacity of the whole allocation.
+    pub cap: usize,
+}
+
+// SAFETY: long must be the representation of a MAByteString or
+// MAByteStringBuilder.
+pub (super) unsafe fn layout(long: &InnerLong) -> Layout {
+    if long.len > isize::MAX as usize {  //inline string
+        let short = unsafe { &*(long as *const InnerLong as *const InnerShort) };
+        return Layout::Short { len_byte: short.len, len: short.len.wrapping_sub(0x80) as usize };
+    }
+    // under loom the control block pointer of a static string is a
+    // placeholder that can't be loaded, see sync::null_cbptr. Otherwise it
+    // is loaded so that check_invariants can spot a corrupt one.
+    let cbptr = if cfg!(loom) && long.cap == 0 { ptr::null_mut() } else { long.cbptr.load(Ordering::Acquire) };
+    let control_block = if cbptr.is_null() || cbptr == CB_INSTALLING {
+        None
+    } else {
+        let cb = unsafe { &*cbptr };
+        let count = cb.count.load(Ordering::Relaxed);
+        Some(ControlBlockLayout {
+            count: count,
+            inline: (count & 1) == 1,
+            strong: count >> 1,
+            weak: cb.weak.load(Ordering::Relaxed),
+            cap: cb.cap,
+        })
+    };
+    return Layout::Long { ptr: long.ptr, len: long.len, cap: long.cap, cbptr: cbptr as *const u8, control_block: control_block };
+}
+
+// panic with a description of the problem and the layout if the string is
+// not in a valid state. Builders may only be short or uniquely owned.
+// SAFETY: as for layout
+#[cfg(any(debug_assertions, feature = "check-invariants"))]
+pub (super) unsafe fn check_invariants(long: &InnerLong, builder: bool) {
+    let layout = unsafe { layout(long) };
+    if let Err(problem) = check(&layout, builder) {
+        panic!("invalid string, {}: {:#?}", problem, layout);
+    }
+}
+
+#[cfg(any(debug_assertions, feature = "check-invariants"))]
+fn check(layout: &Layout, builder: bool) -> Result<(), &'static str> {
+    let (ptr, len, cap, cbptr, control_block) = match *layout {
+        Layout::Short { len_byte, .. } => {
+            if len_byte < 0x80 || len_byte as usize > 0x80 + SHORTLEN {
+                return Err("short length byte out of range");
+            }
+            return Ok(());
+        }
+        Layout::Long { ptr, len, cap, cbptr, ref control_block } => (ptr, len, cap, cbptr, control_block),
+    };
+    if ptr.is_null() { return Err("null data pointer") }
+    if cap == 0 { //static string
+        if builder { return Err("static string in a builder") }
+        if !cbptr.is_null() { return Err("static string with a control block") }
+        return Ok(());
+    }
+    if len > cap { return Err("length greater than capacity") }
+    if cbptr.is_null() { return Ok(()) }
+    if builder { return Err("stale control block in a builder") }
+    // another thread is installing an inline control block.
+    let cb = match control_block { Some(cb) => cb, None => return Ok(()) };
+    // compare pointers rather than addresses, using wrapping arithmetic as
+    // the layout may be corrupt.
+    if cbptr.align_offset(align_of::<ControlBlock>()) != 0 { return Err("misaligned control block") }
+    let cbend = cbptr.wrapping_add(size_of::<ControlBlock>());
+    if cb.inline {
+        if cbptr < ptr.wrapping_add(len) || cbend > ptr.wrapping_add(cap) {
+            return Err("inline control block outside of [ptr+len, ptr+cap)");
+        }
+    } else if cbptr < ptr.wrapping_add(cap) && cbend > ptr {
+        return Err("seperately owned control block inside the buffer");
+    }
+    if cb.strong == 0 { return Err("zero reference count") }
+    if cb.weak == 0 { return Err("zero weak count") }
+    if cb.cap < cap { return Err("control block capacity less than string capacity") }
+    return Ok(());
+}
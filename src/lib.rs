@@ -14,9 +14,10 @@
 //! stage and has only been very lightly tested.
 //!
 //! A MAString or MABytestring is four pointers in size, and can be in one of
-//! five modes, the mode can be checked through the "mode" method.
-//! which returns a string representing the current mode and if the string
-//! is in a shared ownership mode whether or not it is actually shared.
+//! five modes, the mode can be checked through the "mode" method,
+//! which returns a Mode, or the "get_mode" method, which returns a string
+//! representing the current mode and if the string is in a shared ownership
+//! mode whether or not it is actually shared.
 //! 
 //! There are five possible modes.
 //! * Short string ("short"): the string data is stored entirely
@@ -31,9 +32,10 @@
 //!   the string stores a pointer to a reference counted string,
 //!
 //! A uniquely owned MAString will be converted to one with shared
-//! ownership, and a seperate control block by Clone calls. This
-//! means repeatedly cloning a string will result in at-most
-//! a single memory allocation.
+//! ownership by Clone calls. An inline control block is used if there
+//! is room for one after the string data, otherwise a seperate control
+//! block is allocated. This means repeatedly cloning a string will
+//! result in at-most a single memory allocation.
 //!
 //! MAStrings are represented as a union. The short string variant
 //! stores string data and a single byte length. 0x80 is added to
@@ -54,15 +56,25 @@
 //! uniquely owned string to be converted to a shared ownership
 //! string by the clone function.
 //!
-//! The control block is an atomic usize, with the lower
+//! The control block contains an atomic usize, with the lower
 //! bit used to distinguish between seperately owned, and inline
 //! control blocks, and the remaining bits used as a reference
-//! count.
+//! count. It also stores the capacity of the allocation, this allows
+//! strings created by the slice methods to point into the middle
+//! of a shared buffer, while still being able to free it. Finally it
+//! stores a count of weak references, which keep the allocation but
+//! not the string alive, the strong references collectively hold one
+//! weak reference.
 
 #![no_std]
 #![warn(unsafe_op_in_unsafe_fn)]
+// explicit returns and field names are used deliberately throughout the crate.
+#![allow(clippy::needless_return)]
+#![allow(clippy::redundant_field_names)]
+#![cfg_attr(test, allow(clippy::bool_assert_comparison))]
 
 mod limitedusize;
+mod sync;
 mod inner;
 mod bytestring;
 pub use bytestring::MAByteString;
@@ -79,13 +91,56 @@ pub use customcow::CustomCow;
 
 mod stringbuilder;
 pub use stringbuilder::MAStringBuilder;
+#[cfg(feature = "alloc")]
 extern crate alloc;
+#[cfg(any(test, feature = "global-interner"))]
+extern crate std;
+
+#[cfg(feature = "alloc")]
+pub use alloc::collections::TryReserveError;
+#[cfg(not(feature = "alloc"))]
+pub use inner::TryReserveError;
 
 #[doc = include_str!("../README.md")]
-#[cfg(doctest)]
+#[cfg(all(doctest, feature = "alloc"))]
 pub struct ReadmeDoctests;
 
 pub use join::Joinable;
 
+pub use split::SplitPattern;
+pub use split::SplitShared;
+pub use split::LinesShared;
+pub use split::SplitWhitespaceShared;
+pub use weak::MAWeakString;
+pub use weak::MAWeakByteString;
+#[cfg(feature = "alloc")]
+pub use local::MALocalString;
+#[cfg(feature = "alloc")]
+pub use local::MALocalByteString;
+#[cfg(feature = "alloc")]
+pub use interner::MAInterner;
+pub use atomic::AtomicMAString;
+pub use mode::Mode;
+#[cfg(feature = "alloc")]
+pub use memreport::MemoryReport;
+#[cfg(feature = "alloc-stats")]
+pub use allocstats::AllocStats;
+pub use layout::Layout;
+pub use layout::ControlBlockLayout;
+
 mod fromiter;
 mod join;
+mod split;
+mod weak;
+#[cfg(feature = "alloc")]
+mod local;
+#[cfg(feature = "alloc")]
+mod interner;
+mod atomic;
+mod mode;
+#[cfg(feature = "alloc")]
+mod memreport;
+mod allocstats;
+mod layout;
+#[cfg(feature = "testing")]
+pub mod testing;
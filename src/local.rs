@@ -0,0 +1,605 @@
+use core::sync::atomic::Ordering;
+use core::mem::ManuallyDrop;
+use core::mem;
+use core::marker::PhantomData;
+use core::ptr;
+use core::ops::Deref;
+use core::ops::DerefMut;
+use core::ops::Add;
+use core::ops::AddAssign;
+use core::borrow::Borrow;
+use core::hash::Hasher;
+use core::hash::Hash;
+
+use alloc::vec::Vec;
+use alloc::string::String;
+use alloc::str;
+use alloc::fmt;
+use crate::MAByteString;
+use crate::MAByteStringBuilder;
+use crate::MAString;
+use crate::MAStringBuilder;
+use crate::inner::refcount_overflow;
+use crate::inner::Overflow;
+
+/// A MALocalByteString is a variant of MAByteString whose reference count
+/// is not updated atomically, much like Rc is to Arc. It supports the same
+/// five modes, but it can't be sent to or shared with other threads.
+///
+/// Conversions to and from MAByteString only share the buffer if there
+/// are no other owners, otherwise the data is copied.
+pub struct MALocalByteString {
+    // the control block is the same as for MAByteString, but while the
+    // string is owned by MALocalByteStrings only, the reference count is
+    // updated with plain loads and stores. The atomic operations done by
+    // the MAByteString code are still correct, since all owners are on the
+    // same thread.
+    inner: ManuallyDrop<MAByteString>,
+    // a raw pointer, so that we are neither Send nor Sync.
+    _marker: PhantomData<*mut u8>,
+}
+
+impl MALocalByteString {
+    #[inline]
+    const fn wrap(inner: MAByteString) -> Self {
+        MALocalByteString { inner: ManuallyDrop::new(inner), _marker: PhantomData }
+    }
+
+    /// Creates a new MALocalByteString.
+    /// This will not allocate
+    pub const fn new() -> Self {
+        Self::wrap(MAByteString::new())
+    }
+
+    /// Creates a new MALocalByteString with a defined capacity.
+    pub fn with_capacity(cap: usize) -> Self {
+        Self::wrap(MAByteString::with_capacity(cap))
+    }
+
+    /// Creates a MALocalByteString from a slice, see MAByteString::from_slice.
+    pub fn from_slice(s: &[u8]) -> Self {
+        Self::wrap(MAByteString::from_slice(s))
+    }
+
+    /// Creates a MALocalByteString from a Vec, see MAByteString::from_vec.
+    pub fn from_vec(v: Vec<u8>) -> Self {
+        Self::wrap(MAByteString::from_vec(v))
+    }
+
+    /// Create a MALocalByteString from a static reference
+    /// This function will not allocate, and neither will
+    /// Clones of the MALocalByteString thus created.
+    pub const fn from_static(s: &'static [u8]) -> Self {
+        Self::wrap(MAByteString::from_static(s))
+    }
+
+    pub fn from_builder(b : MAByteStringBuilder) -> Self {
+        Self::wrap(MAByteString::from_builder(b))
+    }
+
+    /// Create a MALocalByteString from a MAByteString, this will copy the
+    /// data if the MAByteString shares it's buffer with other owners.
+    pub fn from_mabs(s: MAByteString) -> Self {
+        unsafe {
+            let long = s.long();
+            if long.len <= isize::MAX as usize && long.cap != 0 {
+                let cbptr = long.cbptr.load(Ordering::Acquire);
+                // other owners may be on other threads and rely on the
+                // reference count being atomic.
+                if !cbptr.is_null() && !(*cbptr).is_unique() {
+                    return Self::from_slice(&s);
+                }
+            }
+        }
+        Self::wrap(s)
+    }
+
+    /// Convert the MALocalByteString into a MAByteString, this will copy the
+    /// data if the MALocalByteString shares it's buffer with other owners.
+    pub fn into_mabs(self) -> MAByteString {
+        unsafe {
+            let long = self.inner.long();
+            if long.len <= isize::MAX as usize && long.cap != 0 {
+                let cbptr = long.cbptr.load(Ordering::Relaxed);
+                if !cbptr.is_null() && ((*cbptr).count.load(Ordering::Relaxed) >> 1) > 1 {
+                    return MAByteString::from_slice(&self);
+                }
+            }
+            let result = ptr::read(&*self.inner);
+            mem::forget(self);
+            result
+        }
+    }
+
+    /// Return the current mode of the MALocalByteString (for
+    /// testing/debugging), see MAByteString::get_mode.
+    pub fn get_mode(&self) -> &'static str {
+        self.inner.get_mode()
+    }
+
+    /// ensure there is capacity for at least mincap bytes
+    pub fn reserve(&mut self, mincap: usize) {
+        self.inner.reserve(mincap);
+    }
+
+    /// report the "capacity" of the string, see MAByteString::capacity.
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// Returns the number of bytes of heap memory used by the string, see
+    /// MAByteString::heap_size.
+    pub fn heap_size(&self) -> usize {
+        self.inner.heap_size()
+    }
+
+    // clears the string, see MAByteString::clear.
+    pub fn clear(&mut self) {
+        self.inner.clear();
+    }
+
+    /// convert the MALocalByteString into a Vec, this may allocate.
+    pub fn into_vec(self) -> Vec<u8> {
+        self.into_mabs().into_vec()
+    }
+
+    // Appends a given slice to the end of this bytestring.
+    pub fn push_slice(&mut self, bytestring: &[u8]) {
+        *self += bytestring;
+    }
+}
+
+impl Drop for MALocalByteString {
+    fn drop(&mut self) {
+        unsafe {
+            let long = self.inner.long();
+            if long.len <= isize::MAX as usize && long.cap != 0 {
+                let cbptr = long.cbptr.load(Ordering::Relaxed);
+                if !cbptr.is_null() {
+                    let count = &(*cbptr).count;
+                    let cbval = count.load(Ordering::Relaxed);
+                    if cbval > 3 {
+                        // there are still other references
+                        count.store(cbval - 2, Ordering::Relaxed);
+                        return;
+                    }
+                }
+            }
+            // we are the last owner, let MAByteString free the buffer.
+            ManuallyDrop::drop(&mut self.inner);
+        }
+    }
+}
+
+impl Clone for MALocalByteString {
+    fn clone(&self) -> Self {
+        unsafe {
+            let long = self.inner.long();
+            if long.len > isize::MAX as usize || long.cap == 0 { //short or static string
+                return Self::wrap((*self.inner).clone());
+            }
+            let cbptr = long.get_or_create_cb();
+            let count = &(*cbptr).count;
+            let cbval = count.load(Ordering::Relaxed);
+            if cbval > usize::MAX / 2 {
+                return match refcount_overflow() {
+                    Overflow::Leak => Self::wrap(self.inner.leaked()),
+                    Overflow::Copy => Self::wrap(MAByteString::from_slice(&self.inner)),
+                };
+            }
+            count.store(cbval + 2, Ordering::Relaxed);
+            // the string is now in shared ownership mode, so a copy of it's
+            // fields is a new reference.
+            Self::wrap(ptr::read(&*self.inner))
+        }
+    }
+}
+
+impl Deref for MALocalByteString {
+    type Target = [u8];
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        &self.inner
+    }
+}
+
+impl DerefMut for MALocalByteString {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.inner
+    }
+}
+
+impl Add<&[u8]> for MALocalByteString {
+    type Output = Self;
+    fn add(mut self, rhs: &[u8]) -> Self {
+        self += rhs;
+        self
+    }
+}
+
+impl AddAssign<&[u8]> for MALocalByteString {
+    fn add_assign(&mut self, other: &[u8]) {
+        self.inner.add_assign(other);
+    }
+}
+
+impl fmt::Debug for MALocalByteString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(),fmt::Error> {
+        crate::bytestring::bytes_debug(self, f)
+    }
+}
+
+impl PartialEq for MALocalByteString {
+    fn eq(&self, other : &MALocalByteString) -> bool {
+         return self.deref() == other.deref();
+    }
+}
+impl Eq for MALocalByteString {}
+
+impl PartialEq<&[u8]> for MALocalByteString {
+    fn eq(&self, other : &&[u8]) -> bool {
+         return self.deref() == *other;
+    }
+}
+
+impl PartialEq<MALocalByteString> for &[u8] {
+    fn eq(&self, other : &MALocalByteString) -> bool {
+         return *self == other.deref();
+    }
+}
+
+impl<const N: usize> PartialEq<&[u8;N]> for MALocalByteString {
+    fn eq(&self, other : &&[u8;N]) -> bool {
+         return self.deref() == *other;
+    }
+}
+
+impl<const N: usize> PartialEq<MALocalByteString> for &[u8;N] {
+    fn eq(&self, other : &MALocalByteString) -> bool {
+         return *self == other.deref();
+    }
+}
+
+impl Borrow<[u8]> for MALocalByteString {
+    #[inline]
+    fn borrow(&self) -> &[u8] {
+        self.deref()
+    }
+}
+
+impl Hash for MALocalByteString {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.deref().hash(state);
+    }
+}
+
+impl PartialOrd for MALocalByteString {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MALocalByteString {
+    #[inline]
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.deref().cmp(other.deref())
+    }
+}
+
+impl From<&[u8]> for MALocalByteString {
+    #[inline]
+    fn from(s : &[u8]) -> Self {
+        Self::from_slice(s)
+    }
+}
+
+impl<const N: usize> From<&[u8;N]> for MALocalByteString {
+    #[inline]
+    fn from(s : &[u8;N]) -> Self {
+        Self::from_slice(s)
+    }
+}
+
+impl From<Vec<u8>> for MALocalByteString {
+    #[inline]
+    fn from(s : Vec<u8>) -> Self {
+        Self::from_vec(s)
+    }
+}
+
+impl From<MAByteStringBuilder> for MALocalByteString {
+    #[inline]
+    fn from(s : MAByteStringBuilder) -> Self {
+        Self::from_builder(s)
+    }
+}
+
+impl From<MAByteString> for MALocalByteString {
+    #[inline]
+    fn from(s : MAByteString) -> Self {
+        Self::from_mabs(s)
+    }
+}
+
+impl From<&MALocalByteString> for MALocalByteString {
+    #[inline]
+    fn from(s : &MALocalByteString) -> Self {
+        s.clone()
+    }
+}
+
+impl Default for MALocalByteString {
+    #[inline]
+    fn default() -> MALocalByteString {
+        Self::new()
+    }
+}
+
+/// A MALocalString is a variant of MAString whose reference count is not
+/// updated atomically, see MALocalByteString.
+#[derive(Clone)]
+pub struct MALocalString {
+    inner: MALocalByteString,
+}
+
+impl MALocalString {
+    /// Creates a new MALocalString.
+    pub const fn new() -> Self {
+        MALocalString { inner: MALocalByteString::new() }
+    }
+
+    /// Creates a new MALocalString with a defined capacity.
+    pub fn with_capacity(cap: usize) -> Self {
+        MALocalString { inner: MALocalByteString::with_capacity(cap) }
+    }
+
+    /// Creates a MALocalString from a slice, see MAString::from_slice.
+    pub fn from_slice(s: &str) -> Self {
+        MALocalString { inner: MALocalByteString::from_slice(s.as_bytes()) }
+    }
+
+    /// Creates a MALocalString from a std::String, see MAString::from_string.
+    pub fn from_string(s: String) -> Self {
+        MALocalString { inner: MALocalByteString::from_vec(s.into_bytes()) }
+    }
+
+    /// Create a MALocalString from a static reference
+    /// This function will not allocate, and neither will
+    /// Clones of the MALocalString thus created.
+    pub const fn from_static(s: &'static str) -> Self {
+        MALocalString { inner: MALocalByteString::from_static(s.as_bytes()) }
+    }
+
+    pub fn from_builder(b : MAStringBuilder) -> Self {
+        MALocalString { inner: MALocalByteString::from_builder(b.into_bytes()) }
+    }
+
+    /// Create a MALocalString from a MAString, this will copy the data if
+    /// the MAString shares it's buffer with other owners.
+    pub fn from_mas(s: MAString) -> Self {
+        MALocalString { inner: MALocalByteString::from_mabs(s.into_bytes()) }
+    }
+
+    /// Convert the MALocalString into a MAString, this will copy the data
+    /// if the MALocalString shares it's buffer with other owners.
+    pub fn into_mas(self) -> MAString {
+        MAString { inner: self.inner.into_mabs() }
+    }
+
+    /// Return the current mode of the MALocalString (for testing/debugging)
+    /// see MAString::get_mode.
+    pub fn get_mode(&self) -> &'static str {
+        self.inner.get_mode()
+    }
+
+    /// ensure there is capacity for at least mincap bytes
+    pub fn reserve(&mut self, mincap: usize) {
+        self.inner.reserve(mincap);
+    }
+
+    /// report the "capacity" of the string, see MAString::capacity.
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// Returns the number of bytes of heap memory used by the string, see
+    /// MAByteString::heap_size.
+    pub fn heap_size(&self) -> usize {
+        self.inner.heap_size()
+    }
+
+    // clears the string, see MAString::clear.
+    pub fn clear(&mut self) {
+        self.inner.clear();
+    }
+
+    /// convert the MALocalString into a Std::string, this may allocate.
+    pub fn into_string(self) -> String {
+        unsafe {
+            String::from_utf8_unchecked(self.inner.into_vec())
+        }
+    }
+
+    // Appends a given slice to the end of this string.
+    pub fn push_str(&mut self, string: &str) {
+        *self += string;
+    }
+}
+
+impl Deref for MALocalString {
+   type Target = str;
+   #[inline]
+   fn deref(&self) -> &str {
+        unsafe {
+            str::from_utf8_unchecked(&self.inner)
+        }
+   }
+}
+
+impl DerefMut for MALocalString {
+   #[inline]
+   fn deref_mut(&mut self) -> &mut str {
+        unsafe {
+            str::from_utf8_unchecked_mut(&mut self.inner)
+        }
+   }
+}
+
+impl fmt::Display for MALocalString {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl fmt::Debug for MALocalString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(),fmt::Error> {
+        fmt::Debug::fmt(self.deref(),f)
+    }
+}
+
+impl PartialEq for MALocalString {
+    fn eq(&self, other : &MALocalString) -> bool {
+         return self.deref() == other.deref();
+    }
+}
+impl Eq for MALocalString {}
+
+impl PartialEq<&str> for MALocalString {
+    fn eq(&self, other : &&str) -> bool {
+         return self.deref() == *other;
+    }
+}
+
+impl PartialEq<MALocalString> for &str {
+    fn eq(&self, other : &MALocalString) -> bool {
+         return *self == other.deref();
+    }
+}
+
+impl Add<&str> for MALocalString {
+    type Output = Self;
+    fn add(mut self, rhs: &str) -> Self {
+        self += rhs;
+        self
+    }
+}
+
+impl AddAssign<&str> for MALocalString {
+    fn add_assign(&mut self, other: &str) {
+        self.inner.add_assign(other.as_bytes());
+    }
+}
+
+impl fmt::Write for MALocalString {
+    fn write_str(&mut self, s: &str) -> Result<(), fmt::Error> {
+        *self += s;
+        Ok(())
+    }
+}
+
+impl Borrow<str> for MALocalString {
+    #[inline]
+    fn borrow(&self) -> &str {
+        self.deref()
+    }
+}
+
+impl Hash for MALocalString {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.deref().hash(state);
+    }
+}
+
+impl PartialOrd for MALocalString {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MALocalString {
+    #[inline]
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.deref().cmp(other.deref())
+    }
+}
+
+impl From<&str> for MALocalString {
+    #[inline]
+    fn from(s : &str) -> Self {
+        Self::from_slice(s)
+    }
+}
+
+impl From<String> for MALocalString {
+    #[inline]
+    fn from(s : String) -> Self {
+        Self::from_string(s)
+    }
+}
+
+impl From<MAStringBuilder> for MALocalString {
+    #[inline]
+    fn from(s : MAStringBuilder) -> Self {
+        Self::from_builder(s)
+    }
+}
+
+impl From<MAString> for MALocalString {
+    #[inline]
+    fn from(s : MAString) -> Self {
+        Self::from_mas(s)
+    }
+}
+
+impl From<&MALocalString> for MALocalString {
+    #[inline]
+    fn from(s : &MALocalString) -> Self {
+        s.clone()
+    }
+}
+
+impl Default for MALocalString {
+    #[inline]
+    fn default() -> MALocalString {
+        Self::new()
+    }
+}
+
+/// Convenience macro to create a MALocalByteString, this accepts the same
+/// arguments as mabs!, plus MAByteString and &MALocalByteString values.
+#[macro_export]
+macro_rules! malbs {
+    ($v:literal) => {
+        $crate::MALocalByteString::from_static($v)
+    };
+    ([$($b:expr),+]) => {
+        $crate::MALocalByteString::from_static({
+            const arr: &[u8] = &[$($b),+];
+            arr
+        })
+    };
+    ($v:expr) => {
+        $crate::MALocalByteString::from($v)
+    };
+}
+
+/// Convenience macro to create a MALocalString, this accepts the same
+/// arguments as mas!, plus MAString and &MALocalString values.
+#[macro_export]
+macro_rules! mals {
+    ($v:literal) => {
+        $crate::MALocalString::from_static($v)
+    };
+    ([$($b:expr),+]) => {
+        $crate::MALocalString::from_mas($crate::mas!([$($b),+]))
+    };
+    ($v:expr) => {
+        $crate::MALocalString::from($v)
+    };
+}
@@ -0,0 +1,116 @@
+use alloc::collections::BTreeMap;
+use alloc::collections::btree_map::Entry;
+
+use crate::MAByteString;
+use crate::MAString;
+
+/// MemoryReport totals the heap memory used by a collection of strings,
+/// counting each shared buffer once.
+///
+/// Buffers are identified by the address of the end of their allocation,
+/// which doesn't change when a uniquely owned string is cloned and gains a
+/// control block. The strings added to a report should be kept alive until
+/// it is finished, otherwise the address of a freed buffer may be reused.
+#[derive(Default, Debug)]
+pub struct MemoryReport {
+    // the heap size counted for each buffer, keyed by the end of it's
+    // allocation.
+    seen: BTreeMap<usize, usize>,
+    strings: usize,
+    buffers: usize,
+    heap_size: usize,
+    naive_heap_size: usize,
+}
+
+impl MemoryReport {
+    /// Creates a new, empty, MemoryReport.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a MAByteString to the report.
+    pub fn add_mabs(&mut self, s: &MAByteString) {
+        self.strings += 1;
+        let size = s.heap_size();
+        if size == 0 { return } // short or static string
+        self.naive_heap_size += size;
+        // ptr + cap is the end of the allocation in both the unique and
+        // shared ownership modes, even if ptr is not the start of it.
+        let long = unsafe { s.long() };
+        match self.seen.entry(long.ptr.wrapping_add(long.cap) as usize) {
+            Entry::Vacant(e) => {
+                e.insert(size);
+                self.buffers += 1;
+                self.heap_size += size;
+            }
+            Entry::Occupied(mut e) => {
+                // the buffer may have gained a seperate control block since
+                // it was first seen.
+                if size > *e.get() {
+                    self.heap_size += size - *e.get();
+                    e.insert(size);
+                }
+            }
+        }
+    }
+
+    /// Adds a MAString to the report.
+    pub fn add_mas(&mut self, s: &MAString) {
+        self.add_mabs(&s.inner);
+    }
+
+    /// Returns the number of strings added to the report.
+    pub fn strings(&self) -> usize {
+        self.strings
+    }
+
+    /// Returns the number of distinct heap buffers used by the strings.
+    pub fn buffers(&self) -> usize {
+        self.buffers
+    }
+
+    /// Returns the heap memory used by the strings, counting each buffer
+    /// once.
+    pub fn heap_size(&self) -> usize {
+        self.heap_size
+    }
+
+    /// Returns the sum of heap_size for all the strings, which counts a
+    /// shared buffer once for each string sharing it. The difference from
+    /// heap_size is the memory saved by sharing.
+    pub fn naive_heap_size(&self) -> usize {
+        self.naive_heap_size
+    }
+}
+
+impl<'a> Extend<&'a MAByteString> for MemoryReport {
+    fn extend<I: IntoIterator<Item = &'a MAByteString>>(&mut self, iter: I) {
+        for s in iter {
+            self.add_mabs(s);
+        }
+    }
+}
+
+impl<'a> Extend<&'a MAString> for MemoryReport {
+    fn extend<I: IntoIterator<Item = &'a MAString>>(&mut self, iter: I) {
+        for s in iter {
+            self.add_mas(s);
+        }
+    }
+}
+
+impl<'a> FromIterator<&'a MAByteString> for MemoryReport {
+    fn from_iter<I: IntoIterator<Item = &'a MAByteString>>(iter: I) -> Self {
+        let mut result = Self::new();
+        result.extend(iter);
+        result
+    }
+}
+
+impl<'a> FromIterator<&'a MAString> for MemoryReport {
+    fn from_iter<I: IntoIterator<Item = &'a MAString>>(iter: I) -> Self {
+        let mut result = Self::new();
+        result.extend(iter);
+        result
+    }
+}
@@ -0,0 +1,20 @@
+/// The storage mode of a string, as returned by the mode methods.
+///
+/// Unlike the strings returned by get_mode, this does not say whether a
+/// string in one of the shared ownership modes is actually shared, use
+/// strong_count or is_unique for that.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Mode {
+    /// The string data is stored entirely within the string object.
+    Short,
+    /// The string points to data with static lifetime.
+    Static,
+    /// The string uniquely owns it's buffer.
+    Unique,
+    /// The buffer is reference counted, with the control block stored in
+    /// the same allocation.
+    CbInline,
+    /// The buffer is reference counted, with a seperately allocated
+    /// control block.
+    CbOwned,
+}
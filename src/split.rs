@@ -0,0 +1,313 @@
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use crate::MAString;
+use crate::MAByteString;
+
+/// This trait represents the patterns that can be passed to the
+/// split_shared and split_once_shared methods of MAString and MAByteString.
+///
+/// The standard library's Pattern trait is unstable, so this crate defines
+/// it's own. For MAString it is implemented for char, &str, &String, &[char],
+/// [char; N], &[char; N] and closures taking a char. For MAByteString it is
+/// implemented for u8, &[u8], &[u8; N], `&Vec<u8>` and closures taking a u8,
+/// slices match a contiguous sequence of bytes.
+pub trait SplitPattern<T>
+where
+    T: ?Sized,
+{
+    /// Returns the start and end of the first match of the pattern
+    /// in haystack, if any.
+    fn find_in(&mut self, haystack: &T) -> Option<(usize, usize)>;
+}
+
+impl SplitPattern<str> for char {
+    #[inline]
+    fn find_in(&mut self, haystack: &str) -> Option<(usize, usize)> {
+        haystack.find(*self).map(|p| (p, p + self.len_utf8()))
+    }
+}
+
+impl SplitPattern<str> for &str {
+    #[inline]
+    fn find_in(&mut self, haystack: &str) -> Option<(usize, usize)> {
+        haystack.find(*self).map(|p| (p, p + self.len()))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl SplitPattern<str> for &String {
+    #[inline]
+    fn find_in(&mut self, haystack: &str) -> Option<(usize, usize)> {
+        self.as_str().find_in(haystack)
+    }
+}
+
+impl SplitPattern<str> for &[char] {
+    #[inline]
+    fn find_in(&mut self, haystack: &str) -> Option<(usize, usize)> {
+        let p = haystack.find(*self)?;
+        // the match is a single char, find out how long it is.
+        let c = haystack[p..].chars().next().unwrap();
+        Some((p, p + c.len_utf8()))
+    }
+}
+
+impl<const N: usize> SplitPattern<str> for [char; N] {
+    #[inline]
+    fn find_in(&mut self, haystack: &str) -> Option<(usize, usize)> {
+        (&self[..]).find_in(haystack)
+    }
+}
+
+impl<const N: usize> SplitPattern<str> for &[char; N] {
+    #[inline]
+    fn find_in(&mut self, haystack: &str) -> Option<(usize, usize)> {
+        (&self[..]).find_in(haystack)
+    }
+}
+
+impl<F> SplitPattern<str> for F
+where
+    F: FnMut(char) -> bool,
+{
+    #[inline]
+    fn find_in(&mut self, haystack: &str) -> Option<(usize, usize)> {
+        haystack.char_indices().find(|(_, c)| self(*c)).map(|(p, c)| (p, p + c.len_utf8()))
+    }
+}
+
+impl SplitPattern<[u8]> for u8 {
+    #[inline]
+    fn find_in(&mut self, haystack: &[u8]) -> Option<(usize, usize)> {
+        haystack.iter().position(|b| b == self).map(|p| (p, p + 1))
+    }
+}
+
+impl SplitPattern<[u8]> for &[u8] {
+    fn find_in(&mut self, haystack: &[u8]) -> Option<(usize, usize)> {
+        let len = self.len();
+        // windows panics with a size of zero, an empty pattern matches immediately.
+        if len == 0 { return Some((0, 0)) }
+        haystack.windows(len).position(|w| w == *self).map(|p| (p, p + len))
+    }
+}
+
+impl<const N: usize> SplitPattern<[u8]> for &[u8; N] {
+    #[inline]
+    fn find_in(&mut self, haystack: &[u8]) -> Option<(usize, usize)> {
+        (&self[..]).find_in(haystack)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl SplitPattern<[u8]> for &Vec<u8> {
+    #[inline]
+    fn find_in(&mut self, haystack: &[u8]) -> Option<(usize, usize)> {
+        (&self[..]).find_in(haystack)
+    }
+}
+
+impl<F> SplitPattern<[u8]> for F
+where
+    F: FnMut(u8) -> bool,
+{
+    #[inline]
+    fn find_in(&mut self, haystack: &[u8]) -> Option<(usize, usize)> {
+        haystack.iter().position(|b| self(*b)).map(|p| (p, p + 1))
+    }
+}
+
+// position tracking for SplitShared, this is seperate from the pattern
+// so that the pattern can be borrowed by the find closure.
+struct SplitState {
+    start: usize,
+    searchfrom: usize,
+    finished: bool,
+}
+
+impl SplitState {
+    // returns the range of the next piece. find takes the position to search
+    // from and returns the next match, step takes a position and returns the
+    // length of the character there, so that an empty match is not found
+    // again at the same position.
+    fn next_range<F, G>(&mut self, len: usize, mut find: F, step: G) -> Option<(usize, usize)>
+    where
+        F: FnMut(usize) -> Option<(usize, usize)>,
+        G: Fn(usize) -> usize,
+    {
+        if self.finished { return None }
+        if self.searchfrom <= len {
+            if let Some((matchstart, matchend)) = find(self.searchfrom) {
+                let piece = (self.start, matchstart);
+                self.start = matchend;
+                self.searchfrom = if matchstart == matchend {
+                    matchend + step(matchend)
+                } else {
+                    matchend
+                };
+                return Some(piece);
+            }
+        }
+        self.finished = true;
+        Some((self.start, len))
+    }
+}
+
+/// An iterator over the pieces of a MAString or MAByteString seperated by
+/// a pattern, created by the split_shared methods.
+///
+/// Each piece shares the buffer of the original string, unless it can be
+/// stored as a short string.
+pub struct SplitShared<'a, S, P> {
+    source: &'a S,
+    state: SplitState,
+    pat: P,
+}
+
+impl<'a, S, P> SplitShared<'a, S, P> {
+    pub (super) fn new(source: &'a S, pat: P) -> Self {
+        SplitShared { source: source, state: SplitState { start: 0, searchfrom: 0, finished: false }, pat: pat }
+    }
+}
+
+impl<'a, P> Iterator for SplitShared<'a, MAString, P>
+where
+    P: SplitPattern<str>,
+{
+    type Item = MAString;
+    fn next(&mut self) -> Option<MAString> {
+        let haystack: &'a str = self.source;
+        let pat = &mut self.pat;
+        let (start, end) = self.state.next_range(
+            haystack.len(),
+            |from| pat.find_in(&haystack[from..]).map(|(s, e)| (s + from, e + from)),
+            |p| haystack[p..].chars().next().map_or(1, char::len_utf8),
+        )?;
+        Some(unsafe { self.source.slice_ref_internal(&haystack[start..end]) })
+    }
+}
+
+impl<'a, P> Iterator for SplitShared<'a, MAByteString, P>
+where
+    P: SplitPattern<[u8]>,
+{
+    type Item = MAByteString;
+    fn next(&mut self) -> Option<MAByteString> {
+        let haystack: &'a [u8] = self.source;
+        let pat = &mut self.pat;
+        let (start, end) = self.state.next_range(
+            haystack.len(),
+            |from| pat.find_in(&haystack[from..]).map(|(s, e)| (s + from, e + from)),
+            |_| 1,
+        )?;
+        Some(unsafe { self.source.slice_ref_internal(&haystack[start..end]) })
+    }
+}
+
+/// An iterator over the lines of a MAString or MAByteString, created by the
+/// lines_shared methods.
+///
+/// Lines are ended by either "\n" or "\r\n", which are not included in the
+/// result. Each line shares the buffer of the original string, unless it can
+/// be stored as a short string.
+pub struct LinesShared<'a, S> {
+    source: &'a S,
+    pos: usize,
+}
+
+impl<'a, S> LinesShared<'a, S> {
+    pub (super) fn new(source: &'a S) -> Self {
+        LinesShared { source: source, pos: 0 }
+    }
+
+    // line endings are ascii, so this works on both UTF-8 and arbitrary bytes.
+    fn next_range(&mut self, haystack: &[u8]) -> Option<(usize, usize)> {
+        let len = haystack.len();
+        let start = self.pos;
+        if start >= len { return None }
+        match haystack[start..].iter().position(|b| *b == b'\n') {
+            Some(p) => {
+                let mut end = start + p;
+                self.pos = end + 1;
+                if end > start && haystack[end - 1] == b'\r' { end -= 1 }
+                Some((start, end))
+            }
+            None => {
+                self.pos = len;
+                Some((start, len))
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for LinesShared<'a, MAString> {
+    type Item = MAString;
+    fn next(&mut self) -> Option<MAString> {
+        let haystack: &'a str = self.source;
+        let (start, end) = self.next_range(haystack.as_bytes())?;
+        Some(unsafe { self.source.slice_ref_internal(&haystack[start..end]) })
+    }
+}
+
+impl<'a> Iterator for LinesShared<'a, MAByteString> {
+    type Item = MAByteString;
+    fn next(&mut self) -> Option<MAByteString> {
+        let haystack: &'a [u8] = self.source;
+        let (start, end) = self.next_range(haystack)?;
+        Some(unsafe { self.source.slice_ref_internal(&haystack[start..end]) })
+    }
+}
+
+/// An iterator over the whitespace seperated pieces of a MAString or
+/// MAByteString, created by the split_whitespace_shared methods.
+///
+/// For MAString whitespace is as defined by char::is_whitespace, for
+/// MAByteString only ascii whitespace is considered. Each piece shares the
+/// buffer of the original string, unless it can be stored as a short string.
+pub struct SplitWhitespaceShared<'a, S> {
+    source: &'a S,
+    pos: usize,
+}
+
+impl<'a, S> SplitWhitespaceShared<'a, S> {
+    pub (super) fn new(source: &'a S) -> Self {
+        SplitWhitespaceShared { source: source, pos: 0 }
+    }
+}
+
+impl<'a> Iterator for SplitWhitespaceShared<'a, MAString> {
+    type Item = MAString;
+    fn next(&mut self) -> Option<MAString> {
+        let haystack: &'a str = self.source;
+        let len = haystack.len();
+        let rest = haystack[self.pos..].trim_start();
+        if rest.is_empty() {
+            self.pos = len;
+            return None;
+        }
+        let start = len - rest.len();
+        let end = rest.find(char::is_whitespace).map_or(len, |p| start + p);
+        self.pos = end;
+        Some(unsafe { self.source.slice_ref_internal(&haystack[start..end]) })
+    }
+}
+
+impl<'a> Iterator for SplitWhitespaceShared<'a, MAByteString> {
+    type Item = MAByteString;
+    fn next(&mut self) -> Option<MAByteString> {
+        let haystack: &'a [u8] = self.source;
+        let len = haystack.len();
+        let start = match haystack[self.pos..].iter().position(|b| !b.is_ascii_whitespace()) {
+            Some(p) => self.pos + p,
+            None => {
+                self.pos = len;
+                return None;
+            }
+        };
+        let end = haystack[start..].iter().position(|b| b.is_ascii_whitespace()).map_or(len, |p| start + p);
+        self.pos = end;
+        Some(unsafe { self.source.slice_ref_internal(&haystack[start..end]) })
+    }
+}
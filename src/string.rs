@@ -1,13 +1,18 @@
-use alloc::str;
+use core::str;
+#[cfg(feature = "alloc")]
 use alloc::string::String;
+#[cfg(feature = "alloc")]
 use alloc::string::FromUtf8Error;
-use alloc::fmt;
+use core::fmt;
+#[cfg(feature = "alloc")]
 use alloc::vec::Vec;
+use crate::TryReserveError;
 
 use core::ops::Deref;
 use core::ops::DerefMut;
 use core::ops::Add;
 use core::ops::AddAssign;
+use core::ops::RangeBounds;
 use core::borrow::Borrow;
 use core::hash::Hasher;
 use core::hash::Hash;
@@ -18,6 +23,13 @@ use core::str::FromStr;
 use crate::MAByteString;
 use crate::MAStringBuilder;
 use crate::join;
+use crate::split::SplitPattern;
+use crate::split::SplitShared;
+use crate::split::LinesShared;
+use crate::split::SplitWhitespaceShared;
+use crate::MAWeakString;
+use crate::Mode;
+use crate::Layout;
 
 #[derive(Clone)]
 pub struct MAString {
@@ -31,11 +43,24 @@ impl MAString {
     }
 
     /// Creates a new MAString with a defined capacity, the resulting
-    /// MAString will uniquely own it's buffer.
+    /// MAString will uniquely own it's buffer. Room is also left for
+    /// an inline control block, so cloning will not allocate.
     pub fn with_capacity(cap: usize) -> Self {
         MAString { inner: MAByteString::with_capacity(cap) }
     }
 
+    /// Like with_capacity, but returns an error rather than aborting if
+    /// memory allocation fails.
+    pub fn try_with_capacity(cap: usize) -> Result<Self, TryReserveError> {
+        Ok(MAString { inner: MAByteString::try_with_capacity(cap)? })
+    }
+
+    /// Like with_capacity, but ensures the string has an inline control
+    /// block, see MAByteString::make_shared.
+    pub fn with_capacity_shared(cap: usize) -> Self {
+        MAString { inner: MAByteString::with_capacity_shared(cap) }
+    }
+
     /// Creates a MAString from a slice.
     /// This will allocate if the string cannot be stored as a short string,
     /// the resulting string will be in shared ownership mode with an inline
@@ -44,6 +69,12 @@ impl MAString {
         MAString { inner: MAByteString::from_slice(s.as_bytes()) }
     }
 
+    /// Like from_slice, but returns an error rather than aborting if
+    /// memory allocation fails.
+    pub fn try_from_slice(s: &str) -> Result<Self, TryReserveError> {
+        Ok(MAString { inner: MAByteString::try_from_slice(s.as_bytes())? })
+    }
+
     /// create a MAString from a std::String.
     /// This will not allocate.
     /// If the string can be represented as a  short string then it will be stored
@@ -53,11 +84,21 @@ impl MAString {
     /// be used to createa shared ownership MAString with an inline control block.
     /// if neither of those are possible, then the MAString will have unique
     /// ownership, until it is first Cloned, at which point it will switch to
-    /// shared ownership with an external control block. 
+    /// shared ownership. Clone installs an inline control block if the
+    /// string has grown enough spare capacity for one by then, otherwise it
+    /// allocates an external control block.
+    #[cfg(feature = "alloc")]
     pub fn from_string(s: String) -> Self {
         MAString { inner: MAByteString::from_vec(s.into_bytes()) }
     }
 
+    /// Like from_string, but if the String has no room for an inline control
+    /// block the data is copied to a new buffer which does, see make_shared.
+    #[cfg(feature = "alloc")]
+    pub fn from_string_shared(s: String) -> Self {
+        MAString { inner: MAByteString::from_vec_shared(s.into_bytes()) }
+    }
+
     /// Create a MAByteString from a static reference
     /// This function will not allocate, and neither wil
     /// Clones of the MAString thus created.
@@ -69,11 +110,45 @@ impl MAString {
         MAString { inner: MAByteString::from_builder(b.into_bytes()) }
     }
 
+    /// Like from_builder, but ensures the string has an inline control
+    /// block, see MAByteString::from_builder_shared.
+    pub fn from_builder_shared(b : MAStringBuilder) -> Self {
+        MAString { inner: MAByteString::from_builder_shared(b.into_bytes()) }
+    }
+
     /// Return the current mode of the MAByteString (for testing/debugging)
     pub fn get_mode(&self) -> &'static str {
         self.inner.get_mode()
     }
 
+    /// Return the current mode of the MAString.
+    pub fn mode(&self) -> Mode {
+        self.inner.mode()
+    }
+
+    /// Returns the number of MAStrings sharing the buffer, short, static
+    /// and uniquely owned strings return 1.
+    pub fn strong_count(&self) -> usize {
+        self.inner.strong_count()
+    }
+
+    /// Returns true if the string can be modified without copying it,
+    /// see MAByteString::is_unique.
+    pub fn is_unique(&self) -> bool {
+        self.inner.is_unique()
+    }
+
+    /// Returns the string data if this is a static string.
+    pub fn as_static(&self) -> Option<&'static str> {
+        self.inner.as_static().map(|s| unsafe { str::from_utf8_unchecked(s) })
+    }
+
+    /// Returns true if the two strings point to the same data, as with
+    /// Arc::ptr_eq.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        MAByteString::ptr_eq(&this.inner, &other.inner)
+    }
+
     /// Converts to a mutable string slice.
     pub fn as_mut_str(&mut self) -> &mut str {
         unsafe {
@@ -86,6 +161,23 @@ impl MAString {
         self.inner.reserve(mincap);
     }
 
+    /// Like reserve, but returns an error rather than aborting if memory
+    /// allocation fails.
+    pub fn try_reserve(&mut self, mincap: usize) -> Result<(), TryReserveError> {
+        self.inner.try_reserve(mincap)
+    }
+
+    /// Shrinks the capacity of the string as much as possible, while keeping
+    /// it at least mincap, see MAByteString::shrink_to.
+    pub fn shrink_to(&mut self, mincap: usize) {
+        self.inner.shrink_to(mincap);
+    }
+
+    /// Shrinks the capacity of the string as much as possible.
+    pub fn shrink_to_fit(&mut self) {
+        self.inner.shrink_to_fit();
+    }
+
     /// report the "capacity" of the string. Be aware that due to MAByteStrings
     /// copy on write model, this does not gaurantee that future operations
     /// will not allocate.
@@ -93,6 +185,28 @@ impl MAString {
         self.inner.capacity()
     }
 
+    /// Returns the number of bytes of heap memory used by the string, see
+    /// MAByteString::heap_size.
+    pub fn heap_size(&self) -> usize {
+        self.inner.heap_size()
+    }
+
+    /// Returns a snapshot of the internal representation of the string, for
+    /// debugging.
+    pub fn layout(&self) -> Layout {
+        self.inner.layout()
+    }
+
+    /// Checks that the internal representation of the string is valid and
+    /// that it contains valid UTF-8, see MAByteString::check_invariants.
+    #[cfg(any(debug_assertions, feature = "check-invariants"))]
+    pub fn check_invariants(&self) {
+        self.inner.check_invariants();
+        if let Err(e) = str::from_utf8(&self.inner) {
+            panic!("invalid string, {}: {:#?}", e, self.layout());
+        }
+    }
+
     // clears the string.
     // if the string is in unique ownership mode, or is in shared ownership
     // mode but we are the only owner then this will reset the length but/
@@ -102,18 +216,29 @@ impl MAString {
         self.inner.clear();
     }
 
+    /// Ensure that cloning the string will not allocate, see
+    /// MAByteString::make_shared.
+    pub fn make_shared(&mut self) {
+        self.inner.make_shared();
+    }
+
     /// convert the MAString into a Vec, this may allocate.
+    #[cfg(feature = "alloc")]
     pub fn into_vec(self) -> Vec<u8> {
         self.inner.into_vec()
     }
 
-    /// fills the string with UTF-8 data. SAFETY: It is UB to supply invalid UTF-8
+    /// fills the string with UTF-8 data.
+    ///
+    /// # Safety
+    /// It is UB to supply invalid UTF-8
     pub unsafe fn from_utf8_unchecked(data: impl Into<MAByteString>) -> Self {
         let data = data.into();
         Self { inner: data }
     }
 
     /// fills the string with UTF-8 data, returning an error if it is invalid
+    #[cfg(feature = "alloc")]
     pub fn from_utf8(data: impl Into<MAByteString>) -> Result<Self, FromUtf8Error> {
         let data = data.into();
         match str::from_utf8(&data) {
@@ -123,6 +248,7 @@ impl MAString {
     }
 
     /// fills the string with UTF-8 data, returning an error if it is invalid
+    #[cfg(feature = "alloc")]
     pub fn from_utf8_lossy(data: impl Into<MAByteString>) -> Self {
         let data = data.into();
         match str::from_utf8(&data) {
@@ -132,12 +258,34 @@ impl MAString {
     }
 
     /// convert the MAString into a Std::string, this may allocate.
+    #[cfg(feature = "alloc")]
     pub fn into_string(self) -> String {
         unsafe {
             String::from_utf8_unchecked(self.inner.into_vec())
         }
     }
 
+    /// convert the MAString into a MAStringBuilder if this can be done
+    /// without copying the data, otherwise the string is returned unchanged.
+    /// See MAByteString::try_unwrap.
+    pub fn try_unwrap(self) -> Result<MAStringBuilder, Self> {
+        match self.inner.try_unwrap() {
+            Ok(b) => Ok(MAStringBuilder { inner: b }),
+            Err(s) => Err(MAString { inner: s }),
+        }
+    }
+
+    /// convert the MAString into a String if this can be done without
+    /// copying the data, otherwise the string is returned unchanged.
+    /// See MAByteString::into_vec_if_unique.
+    #[cfg(feature = "alloc")]
+    pub fn into_string_if_unique(self) -> Result<String, Self> {
+        match self.inner.into_vec_if_unique() {
+            Ok(v) => Ok(unsafe { String::from_utf8_unchecked(v) }),
+            Err(s) => Err(MAString { inner: s }),
+        }
+    }
+
     // convert the MAString into a MAByteString
     pub fn into_bytes(self) -> MAByteString {
         self.inner
@@ -167,11 +315,87 @@ impl MAString {
         MAString { inner: result }
     }
 
+    /// Returns a MAString containing the given range of this string.
+    /// If the result can be stored as a short string then the data will be
+    /// copied, otherwise the result will share this string's buffer in the
+    /// same way as Clone, so at most a single memory allocation is needed to
+    /// create a control block if this string is in unique ownership mode.
+    /// Panics if the range is out of bounds or does not lie on char
+    /// boundaries.
+    pub fn slice<R>(&self, range: R) -> Self
+    where
+        R: RangeBounds<usize>,
+    {
+        let sub = &self.deref()[(range.start_bound().cloned(), range.end_bound().cloned())];
+        unsafe { self.slice_ref_internal(sub) }
+    }
+
+    // create a MAString from part of this string's data, sharing
+    // the buffer if the result cannot be stored as a short string.
+    // SAFETY: sub must be a subslice of self.
+    pub (super) unsafe fn slice_ref_internal(&self, sub: &str) -> Self {
+        MAString { inner: unsafe { self.inner.slice_ref_internal(sub.as_bytes()) } }
+    }
+
+    /// Returns an iterator over the pieces of this string seperated by pat,
+    /// like str::split. The pieces share this string's buffer in the same
+    /// way as slice.
+    pub fn split_shared<P>(&self, pat: P) -> SplitShared<'_, MAString, P>
+    where
+        P: SplitPattern<str>,
+    {
+        SplitShared::new(self, pat)
+    }
+
+    /// Returns an iterator over the lines of this string, like str::lines.
+    /// The lines share this string's buffer in the same way as slice.
+    pub fn lines_shared(&self) -> LinesShared<'_, MAString> {
+        LinesShared::new(self)
+    }
+
+    /// Returns an iterator over the whitespace seperated pieces of this
+    /// string, like str::split_whitespace. The pieces share this string's
+    /// buffer in the same way as slice.
+    pub fn split_whitespace_shared(&self) -> SplitWhitespaceShared<'_, MAString> {
+        SplitWhitespaceShared::new(self)
+    }
+
+    /// Splits the string on the first occurrence of pat, like
+    /// str::split_once. The pieces share this string's buffer in the same
+    /// way as slice.
+    pub fn split_once_shared<P>(&self, mut pat: P) -> Option<(MAString, MAString)>
+    where
+        P: SplitPattern<str>,
+    {
+        let (start, end) = pat.find_in(self)?;
+        unsafe { Some((self.slice_ref_internal(&self[..start]), self.slice_ref_internal(&self[end..]))) }
+    }
+
+    /// Creates a weak reference to this string, which can be upgraded back
+    /// to a MAString as long as the string or one of it's clones is still
+    /// alive. If the string is in unique ownership mode it will be switched
+    /// to shared ownership mode, which requires allocating a control block.
+    pub fn downgrade(&self) -> MAWeakString {
+        MAWeakString::new(self)
+    }
+
     // Appends a given slice to the end of this string.
     pub fn push_str(&mut self, string: &str) {
         *self += string;
     }
 
+    /// Like push_str, but returns an error rather than aborting if memory
+    /// allocation fails.
+    pub fn try_push_str(&mut self, string: &str) -> Result<(), TryReserveError> {
+        self.inner.try_push_slice(string.as_bytes())
+    }
+
+    /// Like clone, but returns an error rather than aborting if memory
+    /// allocation for a control block fails.
+    pub fn try_clone(&self) -> Result<Self, TryReserveError> {
+        Ok(MAString { inner: self.inner.try_clone()? })
+    }
+
     // Joins together an iterator of strings, using self as a seperator.
     pub fn join<T,I>(&self, iter : I) -> Self
     where
@@ -271,7 +495,7 @@ impl Hash for MAString {
 impl PartialOrd for MAString {
     #[inline]
     fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
-        self.deref().partial_cmp(other.deref())
+        Some(self.cmp(other))
     }
 }
 
@@ -303,6 +527,7 @@ impl<const N: usize> From<&[char; N]> for MAString {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl From<String> for MAString {
     #[inline]
     fn from(s : String) -> Self {
@@ -317,6 +542,7 @@ impl From<MAStringBuilder> for MAString {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl From<&String> for MAString {
     #[inline]
     fn from(s : &String) -> Self {
@@ -1,14 +1,20 @@
-use alloc::str;
+use core::str;
+#[cfg(feature = "alloc")]
 use alloc::string::String;
+#[cfg(feature = "alloc")]
 use alloc::string::FromUtf8Error;
-use alloc::fmt;
+use core::fmt;
+#[cfg(feature = "alloc")]
 use alloc::vec::Vec;
+use crate::TryReserveError;
 use core::ops::Deref;
 use core::ops::DerefMut;
 use core::ops::Add;
 use core::ops::AddAssign;
 use crate::MAByteStringBuilder;
 use crate::MAString;
+use crate::Mode;
+use crate::Layout;
 use core::borrow::Borrow;
 use core::hash::Hasher;
 use core::hash::Hash;
@@ -28,11 +34,22 @@ impl MAStringBuilder {
         MAStringBuilder { inner: MAByteStringBuilder::new() }
     }
 
-    /// Creates a new MAStringBuilder with a defined capacity
+    /// Creates a new MAStringBuilder with a defined capacity. Room is also
+    /// left for an inline control block after cap bytes, so as long as the
+    /// length does not exceed cap, converting the builder to a MAString will
+    /// not need to allocate one. If the builder may grow beyond cap, use
+    /// MAString::from_builder_shared to make sure the string has an inline
+    /// control block.
     pub fn with_capacity(cap: usize) -> Self {
         MAStringBuilder { inner: MAByteStringBuilder::with_capacity(cap) }
     }
 
+    /// Like with_capacity, but returns an error rather than aborting if
+    /// memory allocation fails.
+    pub fn try_with_capacity(cap: usize) -> Result<Self, TryReserveError> {
+        Ok(MAStringBuilder { inner: MAByteStringBuilder::try_with_capacity(cap)? })
+    }
+
     /// Creates a MAStringBuilder from a slice.
     /// This will allocate if the StringBuilder cannot be stored as a short string,
     /// the resulting string will be in shared ownership mode with an inline
@@ -41,10 +58,17 @@ impl MAStringBuilder {
         MAStringBuilder { inner: MAByteStringBuilder::from_slice(s.as_bytes()) }
     }
 
+    /// Like from_slice, but returns an error rather than aborting if
+    /// memory allocation fails.
+    pub fn try_from_slice(s: &str) -> Result<Self, TryReserveError> {
+        Ok(MAStringBuilder { inner: MAByteStringBuilder::try_from_slice(s.as_bytes())? })
+    }
+
     /// create a MAStringBuilder from a std::String.
     /// This will not allocate.
     /// If the string can be represented as a  short string then it will be stored
     /// as one and the memory owned by the Vec will be freed.
+    #[cfg(feature = "alloc")]
     pub fn from_string(s: String) -> Self {
         MAStringBuilder { inner: MAByteStringBuilder::from_vec(s.into_bytes()) }
     }
@@ -59,6 +83,37 @@ impl MAStringBuilder {
         self.inner.get_mode()
     }
 
+    /// Return the current mode of the MAStringBuilder, this is always
+    /// Short or Unique.
+    pub fn mode(&self) -> Mode {
+        self.inner.mode()
+    }
+
+    /// Returns the number of strings sharing the buffer, builders never
+    /// share their buffer so this is always 1.
+    pub fn strong_count(&self) -> usize {
+        1
+    }
+
+    /// Returns true if the string can be modified without copying it,
+    /// builders never share their buffer so this is always true.
+    pub fn is_unique(&self) -> bool {
+        true
+    }
+
+    /// Returns the string data if this is a static string, builders never
+    /// hold static strings so this is always None.
+    pub fn as_static(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Returns true if the two strings point to the same data, since
+    /// builders never share their buffer this is only true if this and
+    /// other are the same builder.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        MAByteStringBuilder::ptr_eq(&this.inner, &other.inner)
+    }
+
     /// Converts to a mutable string slice.
     pub fn as_mut_str(&mut self) -> &mut str {
         unsafe {
@@ -71,6 +126,23 @@ impl MAStringBuilder {
         self.inner.reserve(mincap);
     }
 
+    /// Like reserve, but returns an error rather than aborting if memory
+    /// allocation fails.
+    pub fn try_reserve(&mut self, mincap: usize) -> Result<(), TryReserveError> {
+        self.inner.try_reserve(mincap)
+    }
+
+    /// Shrinks the capacity of the string as much as possible, while keeping
+    /// it at least mincap, see MAByteStringBuilder::shrink_to.
+    pub fn shrink_to(&mut self, mincap: usize) {
+        self.inner.shrink_to(mincap);
+    }
+
+    /// Shrinks the capacity of the string as much as possible.
+    pub fn shrink_to_fit(&mut self) {
+        self.inner.shrink_to_fit();
+    }
+
     /// report the "capacity" of the string. Be aware that due to MAByteStrings
     /// copy on write model, this does not gaurantee that future operations
     /// will not allocate.
@@ -78,23 +150,51 @@ impl MAStringBuilder {
         self.inner.capacity()
     }
 
+    /// Returns the number of bytes of heap memory used by the string, short
+    /// strings return 0.
+    pub fn heap_size(&self) -> usize {
+        self.inner.heap_size()
+    }
+
+    /// Returns a snapshot of the internal representation of the string, for
+    /// debugging.
+    pub fn layout(&self) -> Layout {
+        self.inner.layout()
+    }
+
+    /// Checks that the internal representation of the string is valid and
+    /// that it contains valid UTF-8, see
+    /// MAByteStringBuilder::check_invariants.
+    #[cfg(any(debug_assertions, feature = "check-invariants"))]
+    pub fn check_invariants(&self) {
+        self.inner.check_invariants();
+        if let Err(e) = str::from_utf8(&self.inner) {
+            panic!("invalid string, {}: {:#?}", e, self.layout());
+        }
+    }
+
     // clears the string, retaining it's capacity.
     pub fn clear(&mut self) {
         self.inner.clear();
     }
 
     /// convert the MAStringBuilder into a Vec, this may allocate.
+    #[cfg(feature = "alloc")]
     pub fn into_vec(self) -> Vec<u8> {
         self.inner.into_vec()
     }
 
-    /// fills the string with UTF-8 data. SAFETY: It is UB to supply invalid UTF-8
+    /// fills the string with UTF-8 data.
+    ///
+    /// # Safety
+    /// It is UB to supply invalid UTF-8
     pub unsafe fn from_utf8_unchecked(data: impl Into<MAByteStringBuilder>) -> Self {
         let data = data.into();
         Self { inner: data }
     }
 
     /// fills the string with UTF-8 data, returning an error if it is invalid
+    #[cfg(feature = "alloc")]
     pub fn from_utf8(data: impl Into<MAByteStringBuilder>) -> Result<Self, FromUtf8Error> {
         let data = data.into();
         match str::from_utf8(&data) {
@@ -104,6 +204,7 @@ impl MAStringBuilder {
     }
 
     /// fills the string with UTF-8 data, returning an error if it is invalid
+    #[cfg(feature = "alloc")]
     pub fn from_utf8_lossy(data: impl Into<MAByteStringBuilder>) -> Self {
         let data = data.into();
         match str::from_utf8(&data) {
@@ -113,6 +214,7 @@ impl MAStringBuilder {
     }
 
     /// convert the MAStringBuilder into a Std::string, this may allocate.
+    #[cfg(feature = "alloc")]
     pub fn into_string(self) -> String {
         unsafe {
             String::from_utf8_unchecked(self.inner.into_vec())
@@ -154,6 +256,18 @@ impl MAStringBuilder {
         *self += stringbuilder;
     }
 
+    /// Like push_str, but returns an error rather than aborting if memory
+    /// allocation fails.
+    pub fn try_push_str(&mut self, stringbuilder: &str) -> Result<(), TryReserveError> {
+        self.inner.try_push_slice(stringbuilder.as_bytes())
+    }
+
+    /// Like clone, but returns an error rather than aborting if memory
+    /// allocation fails.
+    pub fn try_clone(&self) -> Result<Self, TryReserveError> {
+        Ok(MAStringBuilder { inner: self.inner.try_clone()? })
+    }
+
     // Joins together an iterator of strings, using self as a seperator.
     pub fn join<T,I>(&self, iter : I) -> Self
     where
@@ -254,7 +368,7 @@ impl Hash for MAStringBuilder {
 impl PartialOrd for MAStringBuilder {
     #[inline]
     fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
-        self.deref().partial_cmp(other.deref())
+        Some(self.cmp(other))
     }
 }
 
@@ -286,6 +400,7 @@ impl<const N: usize> From<&[char; N]> for MAStringBuilder {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl From<String> for MAStringBuilder {
     #[inline]
     fn from(s : String) -> Self {
@@ -300,6 +415,7 @@ impl From<MAString> for MAStringBuilder {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl From<&String> for MAStringBuilder {
     #[inline]
     fn from(s : &String) -> Self {
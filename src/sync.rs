@@ -0,0 +1,41 @@
+// the atomics used for the control block and control block pointer. When
+// built with --cfg loom these are replaced with loom's, so that the loom
+// tests can explore the possible interleavings of the reference counting.
+// loom's atomics are the same size as core's, so the string layout is
+// unchanged.
+
+#[cfg(not(loom))]
+pub (super) use core::sync::atomic::AtomicPtr;
+#[cfg(not(loom))]
+pub (super) use core::sync::atomic::AtomicUsize;
+#[cfg(not(loom))]
+pub (super) use core::sync::atomic::fence;
+#[cfg(not(loom))]
+pub (super) use core::hint::spin_loop;
+
+#[cfg(loom)]
+pub (super) use loom::sync::atomic::AtomicPtr;
+#[cfg(loom)]
+pub (super) use loom::sync::atomic::AtomicUsize;
+#[cfg(loom)]
+pub (super) use loom::sync::atomic::fence;
+#[cfg(loom)]
+pub (super) use loom::hint::spin_loop;
+
+use crate::inner::ControlBlock;
+
+// the control block pointer of a static string, which has none. This is
+// needed because from_static is a const fn.
+#[cfg(not(loom))]
+pub (super) const fn null_cbptr() -> AtomicPtr<ControlBlock> {
+    AtomicPtr::new(core::ptr::null_mut())
+}
+
+// loom's atomics can't be created in a const fn, so static strings get a
+// placeholder instead, which loom does not know about. Code that may see a
+// static string must check for a capacity of zero before loading the
+// control block pointer, so that the placeholder is never loaded.
+#[cfg(loom)]
+pub (super) const fn null_cbptr() -> AtomicPtr<ControlBlock> {
+    unsafe { core::mem::transmute::<usize, AtomicPtr<ControlBlock>>(0) }
+}
@@ -0,0 +1,150 @@
+//! Support for testing code that uses mastring, available with the testing
+//! feature.
+//!
+//! The mode a string ends up in depends on it's length, how it was created
+//! and what has been done to it since, so it is easy for tests to only
+//! exercise some of them. force_mode creates a copy of a string in a chosen
+//! mode, so that code can be tested with every representation.
+//!
+//! ```
+//! use mastring::MAString;
+//! use mastring::testing::force_mode;
+//! use mastring::testing::MODES;
+//! use mastring::assert_mode;
+//! for mode in MODES {
+//!     // static strings are made by leaking a copy, see force_mode.
+//!     if mode == mastring::Mode::Static { continue }
+//!     let s = force_mode(MAString::from_slice("hello"), mode);
+//!     assert_eq!(s.mode(), mode);
+//!     assert_eq!(s, "hello");
+//! }
+//! let s = force_mode(MAString::from_slice("hello"), mastring::Mode::CbOwned);
+//! let s2 = s.clone();
+//! assert_mode!(s2, "cbowned (shared)");
+//! ```
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::mem::size_of;
+use core::ptr;
+use crate::sync::AtomicPtr;
+use crate::MAByteString;
+use crate::MAByteStringBuilder;
+use crate::MAString;
+use crate::MAStringBuilder;
+use crate::Mode;
+use crate::inner::InnerLong;
+use crate::inner::ControlBlock;
+use crate::inner::SHORTLEN;
+
+/// All the modes, in the order they are listed in Mode.
+pub const MODES: [Mode; 5] = [Mode::Short, Mode::Static, Mode::Unique, Mode::CbInline, Mode::CbOwned];
+
+/// Types that can be put into a chosen mode by force_mode.
+pub trait ForceMode: Sized {
+    /// Returns a string with the same contents in the requested mode, the
+    /// string is not shared with any other. See force_mode.
+    fn force_mode(self, mode: Mode) -> Self;
+}
+
+/// Returns a string with the same contents as s in the requested mode.
+///
+/// Strings of any length can be put into the static, unique and shared
+/// ownership modes, but only strings of up to 31 bytes (on 64-bit
+/// architectures) can be short. Builders can only be short or unique.
+/// The function panics if the mode is not possible.
+///
+/// Static strings are created by leaking a copy of the data, so this should
+/// only be used in tests.
+pub fn force_mode<T: ForceMode>(s: T, mode: Mode) -> T {
+    s.force_mode(mode)
+}
+
+// create the long representation of data in mode, which must not be short.
+fn force_long(data: &[u8], mode: Mode) -> InnerLong {
+    let len = data.len();
+    match mode {
+        Mode::Short => unreachable!(),
+        Mode::Static => {
+            let data : &'static [u8] = Box::leak(Box::from(data));
+            InnerLong { len: len, cap: 0, ptr: data.as_ptr() as *mut u8, cbptr: AtomicPtr::new(ptr::null_mut()) }
+        }
+        Mode::Unique | Mode::CbOwned => {
+            // a capacity of zero means a static string, and with no spare
+            // capacity cloning has to allocate a control block.
+            let mut v = Vec::with_capacity(len.max(1));
+            v.extend_from_slice(data);
+            let long = InnerLong::from_vec(v, false, 0);
+            if mode == Mode::CbOwned { long.get_or_create_cb(); }
+            long
+        }
+        Mode::CbInline => {
+            // leave enough room that the control block fits even if the
+            // buffer is not aligned, which can happen under miri.
+            let mut v = Vec::with_capacity(len + size_of::<ControlBlock>() * 2);
+            v.extend_from_slice(data);
+            InnerLong::from_vec(v, true, 0)
+        }
+    }
+}
+
+impl ForceMode for MAByteString {
+    fn force_mode(self, mode: Mode) -> Self {
+        if mode == Mode::Short {
+            assert!(self.len() <= SHORTLEN, "string too long to be short");
+            return Self::from_slice(&self);
+        }
+        Self::from_long(force_long(&self, mode))
+    }
+}
+
+impl ForceMode for MAString {
+    fn force_mode(self, mode: Mode) -> Self {
+        MAString { inner: self.inner.force_mode(mode) }
+    }
+}
+
+impl ForceMode for MAByteStringBuilder {
+    fn force_mode(self, mode: Mode) -> Self {
+        match mode {
+            Mode::Short => {
+                assert!(self.len() <= SHORTLEN, "string too long to be short");
+                Self::from_slice(&self)
+            }
+            Mode::Unique => Self::from_long(force_long(&self, mode)),
+            _ => panic!("builders can only be short or unique"),
+        }
+    }
+}
+
+impl ForceMode for MAStringBuilder {
+    fn force_mode(self, mode: Mode) -> Self {
+        MAStringBuilder { inner: self.inner.force_mode(mode) }
+    }
+}
+
+/// Asserts that a string is in the mode given by a get_mode string, such as
+/// "cbinline (shared)".
+///
+/// Under miri, Vecs are sometimes not aligned, which can leave no room for an
+/// inline control block. This doesn't affect correctness, but it means the
+/// string may end up in unique or cbowned mode instead, so those are also
+/// accepted for an unaligned string under miri.
+#[macro_export]
+macro_rules! assert_mode {
+    ($s:expr, $expectedmode:expr) => {
+        $crate::testing::check_mode($s.get_mode(), $s.as_ptr(), $expectedmode)
+    }
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn check_mode(mode: &str, _ptr: *const u8, expectedmode: &str) {
+    #[cfg(miri)]
+    if ((_ptr as usize) & (core::mem::align_of::<usize>() - 1)) != 0 {
+        if (mode == "unique") && (expectedmode == "cbinline (unique)") { return }
+        if (mode == "cbowned (unique)") && (expectedmode == "cbinline (unique)") { return }
+        if (mode == "cbowned (shared)") && (expectedmode == "cbinline (shared)") { return }
+    }
+    assert_eq!(mode, expectedmode);
+}
@@ -0,0 +1,161 @@
+use core::sync::atomic::Ordering;
+use core::mem::ManuallyDrop;
+use core::ptr;
+use crate::sync::AtomicUsize;
+use crate::sync::spin_loop;
+use core::fmt;
+
+use crate::MAByteString;
+use crate::MAString;
+use crate::inner::ControlBlock;
+use crate::inner::free_shared;
+use crate::inner::refcount_overflow;
+use crate::inner::Overflow;
+
+/// A weak reference to a MAByteString, created by MAByteString::downgrade.
+///
+/// As with alloc::sync::Weak, a weak reference does not stop the string
+/// from being freed, so upgrade returns None once all the MAByteStrings
+/// sharing the buffer have been dropped. The allocation itself is only
+/// released when the weak references have also been dropped.
+///
+/// Short and static strings do not own a buffer, so weak references to
+/// them can always be upgraded.
+pub struct MAWeakByteString {
+    // for short and static strings this is simply a copy of the string,
+    // otherwise it is a string in shared ownership mode whose reference is
+    // counted in the weak count rather than the strong count.
+    inner: ManuallyDrop<MAByteString>,
+}
+
+// increase the weak count, waiting if it is locked by is_unique. Returns
+// false if the count is too high and the overflow policy is to leak or copy,
+// a weak reference to a copy could never be upgraded, so in both cases the
+// caller should return a static reference to the buffer, which will never
+// be freed.
+unsafe fn increment_weak(cbptr: *mut ControlBlock) -> bool {
+    let weak : &AtomicUsize = unsafe { &(*cbptr).weak };
+    let mut cur = weak.load(Ordering::Relaxed);
+    loop {
+        if cur == usize::MAX {
+            // is_unique only holds the lock for a couple of instructions.
+            spin_loop();
+            cur = weak.load(Ordering::Relaxed);
+            continue;
+        }
+        if cur > usize::MAX / 2 {
+            refcount_overflow();
+            return false;
+        }
+        match weak.compare_exchange_weak(cur, cur + 1, Ordering::Acquire, Ordering::Relaxed) {
+            Ok(_) => return true,
+            Err(old) => cur = old,
+        }
+    }
+}
+
+impl MAWeakByteString {
+    pub (super) fn new(s: &MAByteString) -> Self {
+        unsafe {
+            let len = s.long().len;
+            if len > isize::MAX as usize || s.long().cap == 0 { //short or static string
+                return MAWeakByteString { inner: ManuallyDrop::new(s.clone()) };
+            }
+            let cbptr = s.long().get_or_create_cb();
+            if !increment_weak(cbptr) {
+                return MAWeakByteString { inner: ManuallyDrop::new(s.leaked()) };
+            }
+            // s is now in shared ownership mode, so a copy of it's fields
+            // is a valid weak reference.
+            MAWeakByteString { inner: ManuallyDrop::new(ptr::read(s)) }
+        }
+    }
+
+    /// Attempts to obtain a MAByteString from the weak reference, returns
+    /// None if all the strings sharing the buffer have been dropped.
+    pub fn upgrade(&self) -> Option<MAByteString> {
+        unsafe {
+            let long = self.inner.long();
+            if long.len > isize::MAX as usize || long.cap == 0 { //short or static string
+                return Some((*self.inner).clone());
+            }
+            let count : &AtomicUsize = &(*long.cbptr.load(Ordering::Relaxed)).count;
+            let mut cur = count.load(Ordering::Relaxed);
+            loop {
+                if (cur >> 1) == 0 { return None }
+                if cur > usize::MAX / 2 {
+                    // the strong count can't get back down to zero, so the
+                    // buffer can't be freed while we copy it.
+                    return Some(match refcount_overflow() {
+                        Overflow::Leak => self.inner.leaked(),
+                        Overflow::Copy => MAByteString::from_slice(&self.inner),
+                    });
+                }
+                match count.compare_exchange_weak(cur, cur + 2, Ordering::Acquire, Ordering::Relaxed) {
+                    Ok(_) => break,
+                    Err(old) => cur = old,
+                }
+            }
+            Some(ptr::read(&*self.inner))
+        }
+    }
+}
+
+impl Clone for MAWeakByteString {
+    fn clone(&self) -> Self {
+        unsafe {
+            let long = self.inner.long();
+            if long.len > isize::MAX as usize || long.cap == 0 { //short or static string
+                return MAWeakByteString { inner: self.inner.clone() };
+            }
+            if !increment_weak(long.cbptr.load(Ordering::Relaxed)) {
+                return MAWeakByteString { inner: ManuallyDrop::new(self.inner.leaked()) };
+            }
+            MAWeakByteString { inner: ManuallyDrop::new(ptr::read(&*self.inner)) }
+        }
+    }
+}
+
+impl Drop for MAWeakByteString {
+    fn drop(&mut self) {
+        unsafe {
+            let long = self.inner.long();
+            if long.len > isize::MAX as usize || long.cap == 0 { //short or static string
+                ManuallyDrop::drop(&mut self.inner);
+                return;
+            }
+            free_shared(long.ptr, long.cap, long.cbptr.load(Ordering::Relaxed));
+        }
+    }
+}
+
+impl fmt::Debug for MAWeakByteString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(),fmt::Error> {
+        f.write_str("(MAWeakByteString)")
+    }
+}
+
+/// A weak reference to a MAString, created by MAString::downgrade.
+/// See MAWeakByteString for details.
+#[derive(Clone)]
+pub struct MAWeakString {
+    inner: MAWeakByteString,
+}
+
+impl MAWeakString {
+    pub (super) fn new(s: &MAString) -> Self {
+        MAWeakString { inner: MAWeakByteString::new(&s.inner) }
+    }
+
+    /// Attempts to obtain a MAString from the weak reference, returns
+    /// None if all the strings sharing the buffer have been dropped.
+    pub fn upgrade(&self) -> Option<MAString> {
+        self.inner.upgrade().map(|inner| MAString { inner: inner })
+    }
+}
+
+impl fmt::Debug for MAWeakString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(),fmt::Error> {
+        f.write_str("(MAWeakString)")
+    }
+}
@@ -0,0 +1,89 @@
+use mastring::AllocStats;
+use mastring::MAByteString;
+use mastring::MAString;
+use mastring::MAStringBuilder;
+use mastring::mas;
+
+// the counters are shared by the whole process, so everything is checked
+// from a single test to stop other tests running at the same time.
+#[test]
+fn test_alloc_stats() {
+    assert_eq!(AllocStats::length_bucket(0),0);
+    assert_eq!(AllocStats::length_bucket(1),1);
+    assert_eq!(AllocStats::length_bucket(44),6);
+    assert_eq!(AllocStats::length_bucket(usize::MAX),AllocStats::LENGTH_BUCKETS - 1);
+
+    AllocStats::reset();
+    let start = AllocStats::get();
+    assert_eq!(start.buffers,0);
+    assert!(start.lengths.iter().all(|&c| c == 0));
+
+    let s = MAString::from_slice("the quick brown fox jumped over the lazy dog");
+    let short = MAString::from_slice("short");
+    let stats = AllocStats::get();
+    assert_eq!(stats.buffers,1);
+    assert_eq!(stats.lengths[AllocStats::length_bucket(44)],1);
+    assert_eq!(stats.lengths[AllocStats::length_bucket(5)],1);
+
+    // adopting a String does not allocate, but the first clone needs a
+    // control block.
+    let mut u = MAString::from_string(String::from("the quick brown fox jumped over the lazy dog"));
+    let u2 = u.clone();
+    let u3 = u.clone();
+    let before = AllocStats::get();
+    let stats = before.since(&stats);
+    assert_eq!(stats.buffers,0);
+    assert_eq!(stats.boxed_cbs,1);
+    assert_eq!(stats.lengths[AllocStats::length_bucket(44)],1);
+
+    // modifying a shared string copies it.
+    u.push_str("!");
+    let stats = AllocStats::get().since(&before);
+    assert_eq!(stats.cow_copies,1);
+    assert_eq!(stats.buffers,1);
+    assert_eq!(stats.reallocations,0);
+
+    // as does modifying a static string.
+    let before = AllocStats::get();
+    let mut st = mas!("the quick brown fox jumped over the lazy dog");
+    st.push_str("!");
+    let stats = AllocStats::get().since(&before);
+    assert_eq!(stats.static_promotions,1);
+    assert_eq!(stats.cow_copies,0);
+    assert_eq!(stats.buffers,1);
+
+    // growing a uniquely owned string past it's capacity reallocates.
+    let before = AllocStats::get();
+    let mut b = MAStringBuilder::from_slice("the quick brown fox jumped over the lazy dog");
+    let cap = b.capacity();
+    while b.capacity() == cap {
+        b.push_str("!");
+    }
+    let s2 = MAString::from(b);
+    let stats = AllocStats::get().since(&before);
+    assert_eq!(stats.reallocations,1);
+    assert_eq!(stats.buffers,2);
+    assert_eq!(stats.lengths[AllocStats::length_bucket(s2.len())],1);
+
+    // turning an existing string into a short string doesn't count as
+    // constructing one.
+    let mut a = MAByteString::from_slice(b"the quick brown fox jumped over the lazy dog");
+    let mut c = a.clone();
+    let mut w = MAString::with_capacity(100);
+    w.push_str("short");
+    let before = AllocStats::get();
+    c.truncate(5);
+    c.push_slice(b"!");
+    a.advance(40);
+    w.shrink_to_fit();
+    let stats = AllocStats::get().since(&before);
+    assert_eq!(a.get_mode(),"short");
+    assert_eq!(c.get_mode(),"short");
+    assert_eq!(w.get_mode(),"short");
+    assert_eq!(stats.cow_copies,1);
+    assert!(stats.lengths.iter().all(|&c| c == 0));
+
+    AllocStats::reset();
+    assert_eq!(AllocStats::get().buffers,0);
+    drop((s,short,u2,u3,st,s2));
+}
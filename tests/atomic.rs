@@ -0,0 +1,61 @@
+use mastring::AtomicMAString;
+use mastring::MAString;
+use mastring::mas;
+use std::sync::Arc;
+use std::thread;
+
+static GLOBALSTRING: AtomicMAString = AtomicMAString::new(mas!("hi there"));
+
+#[test]
+fn test_load_store() {
+    let a = AtomicMAString::new(mas!("the quick brown fox jumped over the lazy dog"));
+    let s = a.load();
+    assert_eq!(s,"the quick brown fox jumped over the lazy dog");
+    assert_eq!(s.get_mode(),"static");
+    a.store(MAString::from_string(String::from("the quick brown fox jumped over the lazy cat")));
+    let s2 = a.load();
+    let s3 = a.load();
+    assert_eq!(s2,"the quick brown fox jumped over the lazy cat");
+    assert_eq!(s2.as_ptr(),s3.as_ptr());
+    assert_eq!(s2.get_mode(),"cbowned (shared)");
+    assert_eq!(s,"the quick brown fox jumped over the lazy dog");
+    let old = a.swap(mas!("hello"));
+    assert_eq!(old,"the quick brown fox jumped over the lazy cat");
+    assert_eq!(old.as_ptr(),s2.as_ptr());
+    assert_eq!(a.load(),"hello");
+    assert_eq!(a.into_inner(),"hello");
+
+    assert_eq!(GLOBALSTRING.load(),"hi there");
+}
+
+#[test]
+fn test_compare_exchange() {
+    let a = AtomicMAString::new(mas!("foo"));
+    assert_eq!(a.compare_exchange("bar",mas!("baz")),Err(mas!("foo")));
+    assert_eq!(a.load(),"foo");
+    assert_eq!(a.compare_exchange("foo",mas!("baz")),Ok(mas!("foo")));
+    assert_eq!(a.load(),"baz");
+}
+
+#[test]
+fn test_threads() {
+    let a = Arc::new(AtomicMAString::new(MAString::new()));
+    let mut handles = Vec::new();
+    for t in 0..4 {
+        let a = a.clone();
+        handles.push(thread::spawn(move || {
+            for n in 0..1000 {
+                if t == 0 {
+                    a.store(MAString::from_string(format!("the quick brown fox jumped over the lazy dog {}",n)));
+                } else {
+                    let s = a.load();
+                    assert!(s.is_empty() || s.starts_with("the quick brown fox jumped over the lazy dog "));
+                }
+            }
+        }));
+    }
+    for h in handles {
+        h.join().unwrap();
+    }
+    assert_eq!(a.load(),"the quick brown fox jumped over the lazy dog 999");
+}
@@ -1,5 +1,11 @@
+#![allow(clippy::bool_assert_comparison)]
+// the Hash impls only look at the string contents, not the interior mutable control block pointer.
+#![allow(clippy::mutable_key_type)]
 use mastring::MAByteString;
 use mastring::MAByteStringBuilder;
+use mastring::Mode;
+#[cfg(any(debug_assertions, feature = "check-invariants"))]
+use mastring::Layout;
 use core::mem;
 use core::ops::Deref;
 use core::ops::DerefMut;
@@ -158,6 +164,12 @@ fn test_reserve() {
     assert!(s.capacity() <= "the quick brown fox jumped over the lazy dog".len() + 50);
     //s2 now owns the buffer fomerly owned by s
     assert_mode!(s2,"cbinline (unique)");
+    #[cfg(miri)]
+    // with an unaligned vec from miri there may be no room for the control
+    // block after the reserved space, in which case the clone places it
+    // straight after the data.
+    assert!(s2.capacity() >= 100 - std::mem::size_of::<usize>());
+    #[cfg(not(miri))]
     assert!(s2.capacity() >= 100);
     assert!(s2.capacity() <= 150);
 
@@ -225,7 +237,8 @@ fn test_capacity() {
     let s = MAByteString::from_vec(v);
     assert_mode!(s,"cbinline (unique)");
     assert!(s.capacity() < veccap);
-    assert!(s.capacity() >= veccap - mem::size_of::<usize>()*2);
+    // the control block is three words, plus up to a word of alignment padding.
+    assert!(s.capacity() >= veccap - mem::size_of::<usize>()*4);
 }
 
 #[test]
@@ -411,6 +424,7 @@ fn test_deref_mut() {
 }
 
 #[test]
+#[allow(clippy::assign_op_pattern)] // deliberately testing Add as well as AddAssign
 fn test_add_and_eq() {
     let mut s  = MAByteString::from_static(b"test");
     s = s + b"foo";
@@ -545,3 +559,613 @@ fn test_collect() {
 
 
 }
+
+#[test]
+fn test_slice() {
+    let s = MAByteString::from_slice(b"the quick brown fox jumped over the lazy dog");
+    assert_mode!(s,"cbinline (unique)");
+    let s2 = s.slice(4..9);
+    assert_eq!(s2,b"quick");
+    assert_mode!(s2,"short");
+    assert_mode!(s,"cbinline (unique)");
+    let s2 = s.slice(4..);
+    assert_eq!(s2,b"quick brown fox jumped over the lazy dog");
+    assert_eq!(s2.as_ptr(),s[4..].as_ptr());
+    assert_mode!(s,"cbinline (shared)");
+    assert_mode!(s2,"cbinline (shared)");
+    let s3 = s2.slice(..32);
+    assert_eq!(s3,b"quick brown fox jumped over the ");
+    assert_eq!(s3.as_ptr(),s2.as_ptr());
+    drop(s3);
+    // the slice keeps the buffer alive after the original is dropped.
+    drop(s);
+    assert_mode!(s2,"cbinline (unique)");
+    assert_eq!(s2,b"quick brown fox jumped over the lazy dog");
+
+    let s = MAByteString::from_vec(b"the quick brown fox jumped over the lazy dog".to_vec());
+    assert_mode!(s,"unique");
+    let mut s2 = s.slice(..40);
+    assert_eq!(s2,b"the quick brown fox jumped over the lazy");
+    assert_mode!(s,"cbowned (shared)");
+    assert_mode!(s2,"cbowned (shared)");
+    // modifying a shared slice must copy it.
+    s2 += b" cat";
+    assert_eq!(s2,b"the quick brown fox jumped over the lazy cat");
+    assert_ne!(s2.as_ptr(),s.as_ptr());
+    assert_eq!(s,b"the quick brown fox jumped over the lazy dog");
+    assert_mode!(s,"cbowned (unique)");
+    // once we are the only owner, a slice can be modified in place.
+    let mut s2 = s.slice(..40);
+    drop(s);
+    let ptr = s2.as_ptr();
+    s2 += b"!";
+    assert_eq!(s2,b"the quick brown fox jumped over the lazy!");
+    assert_eq!(s2.as_ptr(),ptr);
+
+    let s = MAByteString::from_static(b"the quick brown fox jumped over the lazy dog");
+    let s2 = s.slice(10..);
+    assert_eq!(s2,b"brown fox jumped over the lazy dog");
+    assert_eq!(s2.as_ptr(),s[10..].as_ptr());
+    assert_mode!(s2,"static");
+
+    let s = MAByteString::from_slice(b"the quick brown fox jumped over the lazy dog");
+    let s2 = s.slice(4..);
+    drop(s);
+    let v = s2.into_vec();
+    assert_eq!(v,b"quick brown fox jumped over the lazy dog");
+
+    let s = MAByteString::from_slice(b"the quick brown fox jumped over the lazy dog");
+    let s2 = s.slice(4..);
+    let b = MAByteStringBuilder::from_mabs(s2);
+    assert_eq!(b,b"quick brown fox jumped over the lazy dog");
+    assert_eq!(b.get_mode(),"unique");
+    drop(s);
+
+    let s = MAByteString::from_slice(b"the quick brown fox jumped over the lazy dog");
+    let s2 = s.slice(4..);
+    drop(s);
+    let b = MAByteStringBuilder::from_mabs(s2);
+    assert_eq!(b,b"quick brown fox jumped over the lazy dog");
+    assert_eq!(b.get_mode(),"unique");
+}
+
+#[test]
+fn test_split_shared() {
+    let s = MAByteString::from_slice(b"the quick brown fox jumped over,the lazy dog jumped over the fox,,end");
+    let v: Vec<MAByteString> = s.split_shared(b',').collect();
+    assert_eq!(v,[&b"the quick brown fox jumped over"[..],b"the lazy dog jumped over the fox",b"",b"end"]);
+    assert_mode!(v[0],"short");
+    assert_eq!(v[1].as_ptr(),s[32..].as_ptr());
+    assert_mode!(v[1],"cbinline (shared)");
+    assert_mode!(v[3],"short");
+
+    let v: Vec<MAByteString> = s.split_shared(b" the ").collect();
+    assert_eq!(v,[&b"the quick brown fox jumped over,the lazy dog jumped over"[..],b"fox,,end"]);
+    let v: Vec<MAByteString> = s.split_shared(|b| b == b'q' || b == b'z').collect();
+    assert_eq!(v,s.split(|b| *b == b'q' || *b == b'z').collect::<Vec<_>>());
+    let v: Vec<MAByteString> = mabs!(b"ab").split_shared(b"").collect();
+    assert_eq!(v,[&b""[..],b"a",b"b",b""]);
+
+    let (a,b) = s.split_once_shared(b",,").unwrap();
+    assert_eq!(a,b"the quick brown fox jumped over,the lazy dog jumped over the fox");
+    assert_eq!(b,b"end");
+    assert_mode!(a,"cbinline (shared)");
+    assert!(s.split_once_shared(b'#').is_none());
+}
+
+#[test]
+fn test_lines_shared() {
+    let s = MAByteString::from_slice(b"the quick brown fox jumped\r\nover the lazy dog\n\nend\n");
+    let v: Vec<MAByteString> = s.lines_shared().collect();
+    assert_eq!(v,[&b"the quick brown fox jumped"[..],b"over the lazy dog",b"",b"end"]);
+    assert_eq!(mabs!(b"").lines_shared().count(),0);
+    assert_eq!(mabs!(b"a\nb").lines_shared().collect::<Vec<_>>(),[&b"a"[..],b"b"]);
+}
+
+#[test]
+fn test_split_whitespace_shared() {
+    let s = MAByteString::from_slice(b"  the quick brown fox\xffjumped over the lazy dog\t\n");
+    let v: Vec<MAByteString> = s.split_whitespace_shared().collect();
+    assert_eq!(v,[&b"the"[..],b"quick",b"brown",b"fox\xffjumped",b"over",b"the",b"lazy",b"dog"]);
+    assert_eq!(mabs!(b" \t ").split_whitespace_shared().count(),0);
+}
+
+#[test]
+fn test_advance() {
+    let mut s = MAByteString::from_slice(b"the quick brown fox jumped over the lazy dog");
+    let ptr = s.as_ptr();
+    s.advance(4);
+    assert_eq!(s,b"quick brown fox jumped over the lazy dog");
+    assert_eq!(s.as_ptr(),ptr.wrapping_add(4));
+    assert_mode!(s,"cbinline (unique)");
+    // the whole buffer is reused once we modify the string.
+    s += b"!";
+    assert_eq!(s,b"quick brown fox jumped over the lazy dog!");
+    s.advance(20);
+    assert_eq!(s,b"ed over the lazy dog!");
+    assert_mode!(s,"short");
+
+    let mut s = MAByteString::from_vec(b"the quick brown fox jumped over the lazy dog".to_vec());
+    assert_mode!(s,"unique");
+    let ptr = s.as_ptr();
+    s.advance(4);
+    assert_eq!(s,b"quick brown fox jumped over the lazy dog");
+    assert_eq!(s.as_ptr(),ptr.wrapping_add(4));
+    assert_mode!(s,"cbowned (unique)");
+    let v = s.into_vec();
+    assert_eq!(v,b"quick brown fox jumped over the lazy dog");
+
+    let mut s = MAByteString::from_static(b"the quick brown fox jumped over the lazy dog");
+    s.advance(4);
+    assert_eq!(s,b"quick brown fox jumped over the lazy dog");
+    assert_mode!(s,"static");
+
+    let mut s = MAByteString::from_slice(b"hello world");
+    s.advance(6);
+    assert_eq!(s,b"world");
+    s.advance(5);
+    assert_eq!(s,b"");
+
+    let mut s = MAByteString::from_vec(b"the quick brown fox jumped over the lazy dog".to_vec());
+    s.advance(s.len());
+    assert_eq!(s,b"");
+    assert_mode!(s,"short");
+}
+
+#[test]
+#[should_panic]
+fn test_advance_out_of_bounds() {
+    let mut s = MAByteString::from_slice(b"hello world");
+    s.advance(12);
+}
+
+#[test]
+fn test_split_to() {
+    let mut s = MAByteString::from_slice(b"the quick brown fox jumped over,the lazy dog jumped over the fox");
+    let ptr = s.as_ptr();
+    let head = s.split_to(32);
+    assert_eq!(head,b"the quick brown fox jumped over,");
+    assert_eq!(s,b"the lazy dog jumped over the fox");
+    assert_eq!(head.as_ptr(),ptr);
+    assert_eq!(s.as_ptr(),ptr.wrapping_add(32));
+    assert_mode!(head,"cbinline (shared)");
+    assert_mode!(s,"cbinline (shared)");
+    drop(head);
+    assert_mode!(s,"cbinline (unique)");
+
+    let mut s = MAByteString::from_vec(b"the quick brown fox jumped over the lazy dog".to_vec());
+    let head = s.split_to(4);
+    assert_eq!(head,b"the ");
+    assert_mode!(head,"short");
+    assert_eq!(s,b"quick brown fox jumped over the lazy dog");
+    assert_mode!(s,"cbowned (unique)");
+
+    let mut s = MAByteString::from_slice(b"hello world");
+    let head = s.split_to(6);
+    assert_eq!(head,b"hello ");
+    assert_eq!(s,b"world");
+}
+
+#[test]
+fn test_split_off() {
+    let mut s = MAByteString::from_slice(b"the quick brown fox jumped over,the lazy dog jumped over the fox");
+    let ptr = s.as_ptr();
+    let tail = s.split_off(32);
+    assert_eq!(s,b"the quick brown fox jumped over,");
+    assert_eq!(tail,b"the lazy dog jumped over the fox");
+    assert_eq!(s.as_ptr(),ptr);
+    assert_eq!(tail.as_ptr(),ptr.wrapping_add(32));
+    assert_mode!(s,"cbinline (shared)");
+    // appending to the first half must not overwrite the second half.
+    s += b"!";
+    assert_eq!(s,b"the quick brown fox jumped over,!");
+    assert_eq!(tail,b"the lazy dog jumped over the fox");
+
+    let mut s = MAByteString::from_slice(b"hello world");
+    let tail = s.split_off(5);
+    assert_eq!(s,b"hello");
+    assert_eq!(tail,b" world");
+}
+
+#[test]
+fn test_truncate() {
+    let mut s = MAByteString::from_slice(b"hello world");
+    s.truncate(20);
+    assert_eq!(s,b"hello world");
+    s.truncate(5);
+    assert_eq!(s,b"hello");
+    assert_mode!(s,"short");
+
+    let mut s = MAByteString::from_slice(b"the quick brown fox jumped over the lazy dog");
+    let capacity = s.capacity();
+    s.truncate(9);
+    assert_eq!(s,b"the quick");
+    assert_mode!(s,"cbinline (unique)");
+    assert_eq!(s.capacity(),capacity);
+
+    let mut s = MAByteString::from_static(b"the quick brown fox jumped over the lazy dog");
+    s.truncate(9);
+    assert_eq!(s,b"the quick");
+    assert_mode!(s,"static");
+}
+
+#[test]
+fn test_downgrade() {
+    let s = MAByteString::from_slice(b"the quick brown fox jumped over the lazy dog");
+    let w = s.downgrade();
+    let s2 = w.upgrade().unwrap();
+    assert_eq!(s2,b"the quick brown fox jumped over the lazy dog");
+    assert_eq!(s2.as_ptr(),s.as_ptr());
+    assert_mode!(s,"cbinline (shared)");
+    let w2 = w.clone();
+    drop(s);
+    drop(w);
+    assert_eq!(w2.upgrade().unwrap(),b"the quick brown fox jumped over the lazy dog");
+    drop(s2);
+    assert!(w2.upgrade().is_none());
+
+    let s = MAByteString::from_vec(b"the quick brown fox jumped over the lazy dog".to_vec());
+    assert_mode!(s,"unique");
+    let w = s.downgrade();
+    assert_mode!(s,"cbowned (unique)");
+    drop(s);
+    assert!(w.upgrade().is_none());
+
+    // a string with weak references must not be modified in place.
+    let mut s = MAByteString::from_slice(b"the quick brown fox jumped over the lazy dog");
+    let w = s.downgrade();
+    s += b"!";
+    assert_eq!(s,b"the quick brown fox jumped over the lazy dog!");
+    assert!(w.upgrade().is_none());
+    let w = s.downgrade();
+    let s2 = s.clone();
+    s.clear();
+    assert_eq!(s,b"");
+    assert_eq!(w.upgrade().unwrap(),b"the quick brown fox jumped over the lazy dog!");
+    drop(s2);
+    assert!(w.upgrade().is_none());
+    let s = MAByteString::from_slice(b"the quick brown fox jumped over the lazy dog");
+    let w = s.downgrade();
+    let v = s.into_vec();
+    assert!(w.upgrade().is_none());
+    assert_eq!(v,b"the quick brown fox jumped over the lazy dog");
+
+    // short and static strings can always be upgraded.
+    let s = MAByteString::from_slice(b"hello");
+    let w = s.downgrade();
+    drop(s);
+    assert_eq!(w.upgrade().unwrap(),b"hello");
+    let s = MAByteString::from_static(b"the quick brown fox jumped over the lazy dog");
+    let w = s.downgrade();
+    drop(s);
+    let s = w.upgrade().unwrap();
+    assert_eq!(s,b"the quick brown fox jumped over the lazy dog");
+    assert_mode!(s,"static");
+}
+
+#[test]
+fn test_try() {
+    let mut s = MAByteString::try_from_slice(b"the quick brown fox jumped over the lazy dog").unwrap();
+    assert_eq!(s,b"the quick brown fox jumped over the lazy dog");
+    assert_mode!(s,"cbinline (unique)");
+    assert!(s.try_reserve(usize::MAX).is_err());
+    assert!(s.try_push_slice(&[]).is_ok());
+    s.try_push_slice(b"!").unwrap();
+    assert_eq!(s,b"the quick brown fox jumped over the lazy dog!");
+    s.try_reserve(100).unwrap();
+    assert!(s.capacity() >= 100);
+
+    let s = MAByteString::from_vec(b"the quick brown fox jumped over the lazy dog".to_vec());
+    assert_mode!(s,"unique");
+    let s2 = s.try_clone().unwrap();
+    assert_eq!(s2.as_ptr(),s.as_ptr());
+    assert_mode!(s,"cbowned (shared)");
+
+    let mut s = MAByteString::try_with_capacity(10).unwrap();
+    assert_mode!(s,"short");
+    assert!(s.try_reserve(usize::MAX).is_err());
+    assert!(s.try_push_slice(b"hello").is_ok());
+    assert_eq!(s,b"hello");
+    assert!(MAByteString::try_with_capacity(usize::MAX).is_err());
+}
+
+#[test]
+fn test_mode_and_counts() {
+    let s = MAByteString::from_slice(b"short");
+    assert_eq!(s.mode(),Mode::Short);
+    assert_eq!(s.strong_count(),1);
+    assert_eq!(s.is_unique(),true);
+    assert_eq!(s.as_static(),None);
+    assert!(MAByteString::ptr_eq(&s,&s));
+    assert!(!MAByteString::ptr_eq(&s,&s.clone()));
+
+    let s = MAByteString::from_static(b"the quick brown fox jumped over the lazy dog");
+    assert_eq!(s.mode(),Mode::Static);
+    assert_eq!(s.strong_count(),1);
+    assert_eq!(s.is_unique(),false);
+    assert_eq!(s.as_static(),Some(&b"the quick brown fox jumped over the lazy dog"[..]));
+    assert!(MAByteString::ptr_eq(&s,&s.clone()));
+
+    let mut v = Vec::with_capacity(44);
+    v.extend_from_slice(b"the quick brown fox jumped over the lazy dog");
+    let s = MAByteString::from_vec(v);
+    assert_eq!(s.mode(),Mode::Unique);
+    assert_eq!(s.strong_count(),1);
+    assert_eq!(s.is_unique(),true);
+    assert_eq!(s.as_static(),None);
+    let s2 = s.clone();
+    assert_eq!(s.mode(),Mode::CbOwned);
+    assert_eq!(s.strong_count(),2);
+    assert_eq!(s.is_unique(),false);
+    assert!(MAByteString::ptr_eq(&s,&s2));
+    let w = s2.downgrade();
+    drop(s2);
+    assert_eq!(s.strong_count(),1);
+    // the weak reference means the buffer isn't unique.
+    assert_eq!(s.is_unique(),false);
+    drop(w);
+    assert_eq!(s.is_unique(),true);
+    assert!(!MAByteString::ptr_eq(&s,&MAByteString::from_slice(&s)));
+    assert!(!MAByteString::ptr_eq(&s,&s.slice(1..)));
+}
+
+#[test]
+fn test_try_unwrap() {
+    let s = MAByteString::from_slice(b"the quick brown fox jumped over the lazy dog");
+    let ptr = s.as_ptr();
+    let s2 = s.clone();
+    let s = s.try_unwrap().unwrap_err();
+    assert_eq!(s.as_ptr(),ptr);
+    let s = s.into_vec_if_unique().unwrap_err();
+    drop(s2);
+    let b = s.try_unwrap().unwrap();
+    assert_eq!(b,b"the quick brown fox jumped over the lazy dog");
+    assert_eq!(b.as_ptr(),ptr);
+    assert_eq!(b.mode(),Mode::Unique);
+
+    let s = MAByteString::from_slice(b"the quick brown fox jumped over the lazy dog");
+    let ptr = s.as_ptr();
+    let w = s.downgrade();
+    let s = s.into_vec_if_unique().unwrap_err();
+    drop(w);
+    let v = s.into_vec_if_unique().unwrap();
+    assert_eq!(v,b"the quick brown fox jumped over the lazy dog");
+    assert_eq!(v.as_ptr(),ptr);
+
+    let s = mabs!(b"the quick brown fox jumped over the lazy dog");
+    let s = s.try_unwrap().unwrap_err();
+    assert_eq!(s.mode(),Mode::Static);
+    assert_eq!(s.into_vec_if_unique().unwrap_err().mode(),Mode::Static);
+
+    let s = MAByteString::from_slice(b"short");
+    let s = s.into_vec_if_unique().unwrap_err();
+    let b = s.try_unwrap().unwrap();
+    assert_eq!(b,b"short");
+    assert_eq!(b.mode(),Mode::Short);
+
+    // a slice that is the only owner of it's buffer would have to be moved
+    // to the start of it.
+    let s = MAByteString::from_slice(&[7;100]).slice(10..);
+    assert_eq!(s.is_unique(),true);
+    let ptr = s.as_ptr();
+    let s = s.into_vec_if_unique().unwrap_err();
+    let s = s.try_unwrap().unwrap_err();
+    assert_eq!(s.as_ptr(),ptr);
+    assert_eq!(s,&[7;90][..]);
+    let s = MAByteString::from_slice(&[7;100]).slice(..90);
+    let ptr = s.as_ptr();
+    let v = s.into_vec_if_unique().unwrap();
+    assert_eq!(v.as_ptr(),ptr);
+    assert_eq!(v,&[7;90][..]);
+}
+
+#[test]
+fn test_clone_inline_cb() {
+    let mut v = Vec::with_capacity(100);
+    v.extend_from_slice(b"the quick brown fox jumped over the lazy dog");
+    let mut s = MAByteString::from_vec(v);
+    assert_mode!(s,"cbinline (unique)");
+    let cap = s.capacity();
+    // using the space taken by the control block turns the string back
+    // into a unique string, with room after the data.
+    s.reserve(cap + 1);
+    assert_mode!(s,"unique");
+    // so clone should put the control block there rather than allocating.
+    let s2 = s.clone();
+    assert_mode!(s,"cbinline (shared)");
+    assert_eq!(s2.as_ptr(),s.as_ptr());
+    assert!(s.capacity() < cap + 1);
+    drop(s2);
+    assert_mode!(s,"cbinline (unique)");
+
+    // with no room after the data, clone has to allocate a control block.
+    let s = MAByteString::from_vec(b"the quick brown fox jumped over the lazy dog".to_vec());
+    assert_mode!(s,"unique");
+    let _s2 = s.clone();
+    assert_mode!(s,"cbowned (shared)");
+}
+
+#[test]
+fn test_clone_inline_cb_threads() {
+    // several threads cloning a unique string at once must agree on a
+    // single control block.
+    for _ in 0..100 {
+        let mut v = Vec::with_capacity(100);
+        v.extend_from_slice(b"the quick brown fox jumped over the lazy dog");
+        let mut s = MAByteString::from_vec(v);
+        s.reserve(s.capacity() + 1);
+        assert_mode!(s,"unique");
+        let clones: Vec<Vec<MAByteString>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4).map(|_| scope.spawn(|| (0..10).map(|_| s.clone()).collect())).collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(s.strong_count(),41);
+        for c in clones.iter().flatten() {
+            assert_eq!(c.as_ptr(),s.as_ptr());
+        }
+        drop(clones);
+        assert_eq!(s.strong_count(),1);
+    }
+}
+
+#[test]
+fn test_make_shared() {
+    // no room for a control block, so the data must be copied.
+    let v = b"the quick brown fox jumped over the lazy dog".to_vec();
+    let ptr = v.as_ptr();
+    let s = MAByteString::from_vec_shared(v);
+    assert_mode!(s,"cbinline (unique)");
+    assert_ne!(s.as_ptr(),ptr);
+    let s2 = s.clone();
+    assert_mode!(s2,"cbinline (shared)");
+
+    // room for a control block, so the buffer is kept.
+    let mut v = Vec::with_capacity(100);
+    v.extend_from_slice(b"the quick brown fox jumped over the lazy dog");
+    let ptr = v.as_ptr();
+    let mut s = MAByteString::from_vec(v);
+    s.reserve(s.capacity() + 1);
+    assert_mode!(s,"unique");
+    s.make_shared();
+    assert_mode!(s,"cbinline (unique)");
+    assert_eq!(s.as_ptr(),ptr);
+
+    let mut s = MAByteString::from_slice(b"short");
+    s.make_shared();
+    assert_mode!(s,"short");
+    let mut s = mabs!(b"the quick brown fox jumped over the lazy dog");
+    s.make_shared();
+    assert_mode!(s,"static");
+    let mut s = MAByteString::from_vec(b"the quick brown fox jumped over the lazy dog".to_vec());
+    let _s2 = s.clone();
+    s.make_shared();
+    assert_mode!(s,"cbowned (shared)");
+
+    let s = MAByteString::with_capacity_shared(100);
+    assert_mode!(s,"cbinline (unique)");
+    let mut b = MAByteStringBuilder::with_capacity(64);
+    b.push_slice(&[b'a';65]);
+    let s = MAByteString::from_builder(b);
+    assert_mode!(s,"unique");
+    let b = MAByteStringBuilder::from_mabs(s);
+    let s = MAByteString::from_builder_shared(b);
+    assert_mode!(s,"cbinline (unique)");
+    assert_eq!(s,&[b'a';65][..]);
+}
+
+#[test]
+fn test_shrink() {
+    let mut s = MAByteString::with_capacity(1000);
+    s.push_slice(b"the quick brown fox jumped over the lazy dog");
+    assert!(s.capacity() >= 1000);
+    s.shrink_to(100);
+    assert!(s.capacity() >= 100);
+    assert!(s.capacity() < 1000);
+    assert_mode!(s,"cbinline (unique)");
+    s.shrink_to_fit();
+    assert!(s.capacity() >= s.len());
+    assert!(s.capacity() < 100);
+    assert_mode!(s,"cbinline (unique)");
+    assert_eq!(s,b"the quick brown fox jumped over the lazy dog");
+    let ptr = s.as_ptr();
+    // already as small as it will get.
+    s.shrink_to_fit();
+    assert_eq!(s.as_ptr(),ptr);
+
+    // shared buffers are left alone.
+    let s2 = s.clone();
+    s.truncate(5);
+    s.shrink_to_fit();
+    assert_eq!(s.as_ptr(),ptr);
+    assert_mode!(s,"cbinline (shared)");
+    drop(s2);
+    s.shrink_to(50);
+    assert_mode!(s,"cbinline (unique)");
+    s.shrink_to_fit();
+    assert_mode!(s,"short");
+    assert_eq!(s,b"the q");
+
+    let mut s = mabs!(b"the quick brown fox jumped over the lazy dog");
+    s.shrink_to_fit();
+    assert_mode!(s,"static");
+}
+
+#[test]
+fn test_copy_on_write_to_short() {
+    let mut s = MAByteString::from_slice(b"the quick brown fox jumped over the lazy dog");
+    s.truncate(5);
+    let s2 = s.clone();
+    assert_mode!(s,"cbinline (shared)");
+    s.reserve(10);
+    assert_mode!(s,"short");
+    assert_eq!(s,b"the q");
+    let mut s3 = s2.clone();
+    s3.push_slice(b"uick");
+    assert_mode!(s3,"short");
+    assert_eq!(s3,b"the quick");
+    let mut s4 = s2.clone();
+    s4[0] = b'T';
+    assert_mode!(s4,"short");
+    assert_eq!(s4,b"The q");
+    assert_eq!(s2,b"the q");
+    let b = MAByteStringBuilder::from_mabs(s2.clone());
+    assert_eq!(b.get_mode(),"short");
+    // when the result doesn't fit, a new buffer is still allocated.
+    let mut s5 = s2.clone();
+    s5.push_slice(b"uick brown fox jumped over the lazy dog");
+    assert_mode!(s5,"cbinline (unique)");
+    assert_ne!(s5.as_ptr(),s2.as_ptr());
+
+    let mut s = MAByteString::from_static(b"the quick brown fox jumped over the lazy dog");
+    s.truncate(3);
+    assert_mode!(s,"static");
+    s.push_slice(b"!");
+    assert_mode!(s,"short");
+    assert_eq!(s,b"the!");
+}
+
+#[cfg(any(debug_assertions, feature = "check-invariants"))]
+#[test]
+fn test_check_invariants() {
+    let s = MAByteString::from_slice(b"short");
+    s.check_invariants();
+    assert!(matches!(s.layout(),Layout::Short { len_byte: 0x85, len: 5 }));
+    let s = mabs!(b"the quick brown fox jumped over the lazy dog");
+    s.check_invariants();
+    assert!(matches!(s.layout(),Layout::Long { cap: 0, .. }));
+    let s = MAByteString::from_vec(b"the quick brown fox jumped over the lazy dog".to_vec());
+    s.check_invariants();
+    let s2 = s.clone();
+    s2.check_invariants();
+    let s3 = s2.slice(4..40);
+    s3.check_invariants();
+    match s3.layout() {
+        Layout::Long { control_block: Some(cb), .. } => {
+            assert_eq!(cb.strong,3);
+            assert_eq!(cb.weak,1);
+        }
+        layout => panic!("unexpected layout {:#?}",layout),
+    }
+    let mut s = MAByteString::from_slice(b"the quick brown fox jumped over the lazy dog");
+    s.check_invariants();
+    let _w = s.downgrade();
+    s.push_slice(b"!");
+    s.check_invariants();
+    // the layout dump includes the control block.
+    let dump = format!("{:#?}",s.clone().layout());
+    assert!(dump.contains("control_block: Some("));
+}
+
+#[cfg(target_endian = "little")]
+#[cfg(any(debug_assertions, feature = "check-invariants"))]
+#[test]
+#[should_panic(expected = "static string with a control block")]
+#[cfg_attr(miri, ignore)]
+fn test_check_invariants_corrupt() {
+    let data = b"the quick brown fox jumped over the lazy dog";
+    let cb = [2usize, 1, 0];
+    // cap, ptr, cbptr, len
+    let raw = [0usize, data.as_ptr() as usize, cb.as_ptr() as usize, data.len()];
+    let s : mem::ManuallyDrop<MAByteString> = unsafe { mem::transmute(raw) };
+    s.check_invariants();
+}
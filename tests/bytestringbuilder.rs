@@ -1,5 +1,11 @@
+#![allow(clippy::bool_assert_comparison)]
+// the Hash impls only look at the string contents, not the interior mutable control block pointer.
+#![allow(clippy::mutable_key_type)]
 use mastring::MAByteStringBuilder;
 use mastring::MAByteString;
+use mastring::Mode;
+#[cfg(any(debug_assertions, feature = "check-invariants"))]
+use mastring::Layout;
 use core::mem;
 use core::ops::Deref;
 use core::ops::DerefMut;
@@ -128,7 +134,8 @@ fn test_reserve() {
     assert_eq!(s,b"the quick brown fox jumped over the lazy dog");
     assert_eq!(s.get_mode(),"unique");
     assert!(s.capacity() >= 200);
-    assert!(s.capacity() <= 250);
+    // growth is exponential, and also leaves room for a control block.
+    assert!(s.capacity() <= 320);
 }
 
 #[test]
@@ -256,6 +263,7 @@ fn test_deref_mut() {
 }
 
 #[test]
+#[allow(clippy::assign_op_pattern)] // deliberately testing Add as well as AddAssign
 fn test_add_and_eq() {
     let mut s  = MAByteStringBuilder::from_slice(b"test");
     s = s + b"foo";
@@ -385,3 +393,71 @@ fn test_collect() {
 
 
 }
+
+#[test]
+fn test_try() {
+    let mut s = MAByteStringBuilder::try_from_slice(b"the quick brown fox jumped over the lazy dog").unwrap();
+    assert_eq!(s,b"the quick brown fox jumped over the lazy dog");
+    assert!(s.try_reserve(usize::MAX).is_err());
+    s.try_push_slice(b"!").unwrap();
+    assert_eq!(s,b"the quick brown fox jumped over the lazy dog!");
+    let s2 = s.try_clone().unwrap();
+    assert_eq!(s2,s);
+    assert_ne!(s2.as_ptr(),s.as_ptr());
+    let mut s = MAByteStringBuilder::try_with_capacity(100).unwrap();
+    assert!(s.capacity() >= 100);
+    assert!(MAByteStringBuilder::new().try_reserve(usize::MAX).is_err());
+    s.try_push_slice(b"hello").unwrap();
+    assert_eq!(s,b"hello");
+}
+
+#[test]
+fn test_mode_and_counts() {
+    let s = MAByteStringBuilder::from_slice(b"short");
+    assert_eq!(s.mode(),Mode::Short);
+    let s = MAByteStringBuilder::from_slice(b"the quick brown fox jumped over the lazy dog");
+    assert_eq!(s.mode(),Mode::Unique);
+    assert_eq!(s.strong_count(),1);
+    assert_eq!(s.is_unique(),true);
+    assert_eq!(s.as_static(),None);
+    assert!(MAByteStringBuilder::ptr_eq(&s,&s));
+    assert!(!MAByteStringBuilder::ptr_eq(&s,&s.clone()));
+}
+
+#[test]
+fn test_shrink() {
+    let mut s = MAByteStringBuilder::with_capacity(1000);
+    s.push_slice(b"the quick brown fox jumped over the lazy dog");
+    s.shrink_to(100);
+    assert!(s.capacity() >= 100);
+    assert!(s.capacity() < 1000);
+    s.shrink_to_fit();
+    assert!(s.capacity() < 100);
+    assert_eq!(s.get_mode(),"unique");
+    assert_eq!(s,b"the quick brown fox jumped over the lazy dog");
+    // the shrunk buffer still has room for an inline control block.
+    let s = MAByteString::from_builder(s);
+    // miri sometimes gives us unaligned buffers, which leaves no room.
+    #[cfg(not(miri))]
+    assert_eq!(s.mode(),Mode::CbInline);
+    let mut s = MAByteStringBuilder::from_mabs(s);
+    s.clear();
+    s.shrink_to_fit();
+    assert_eq!(s.get_mode(),"short");
+}
+
+#[cfg(any(debug_assertions, feature = "check-invariants"))]
+#[test]
+fn test_check_invariants() {
+    let mut s = MAByteStringBuilder::from_slice(b"short");
+    s.check_invariants();
+    s.push_slice(b" and now the quick brown fox jumped over the lazy dog");
+    s.check_invariants();
+    assert!(matches!(s.layout(),Layout::Long { control_block: None, .. }));
+    let s = MAByteStringBuilder::from_mabs(mastring::mabs!(b"the quick brown fox jumped over the lazy dog"));
+    s.check_invariants();
+    let s = MAByteString::from_slice(b"the quick brown fox jumped over the lazy dog");
+    let _s2 = s.clone();
+    let s = MAByteStringBuilder::from_mabs(s);
+    s.check_invariants();
+}
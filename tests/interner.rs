@@ -0,0 +1,101 @@
+use mastring::MAInterner;
+use mastring::MAString;
+use mastring::assert_mode;
+
+#[test]
+fn test_intern() {
+    let mut interner = MAInterner::new();
+    assert!(interner.is_empty());
+    let s = interner.intern("the quick brown fox jumped over the lazy dog");
+    let s2 = interner.intern(&String::from("the quick brown fox jumped over the lazy dog"));
+    assert_eq!(s,"the quick brown fox jumped over the lazy dog");
+    assert_eq!(s2.as_ptr(),s.as_ptr());
+    assert_mode!(s,"cbinline (shared)");
+    assert_eq!(interner.len(),1);
+
+    let s3 = interner.intern_mas(MAString::from_string(String::from("the quick brown fox jumped over the lazy dog")));
+    assert_eq!(s3.as_ptr(),s.as_ptr());
+    assert_eq!(interner.get("the quick brown fox jumped over the lazy dog").unwrap().as_ptr(),s.as_ptr());
+    assert!(interner.get("the quick brown fox jumped over the lazy cat").is_none());
+
+    // short strings are not stored.
+    let s4 = interner.intern("hello");
+    assert_eq!(s4,"hello");
+    assert_eq!(s4.get_mode(),"short");
+    assert_eq!(interner.len(),1);
+}
+
+#[test]
+fn test_intern_static() {
+    static TEXT: &str = "the quick brown fox jumped over the lazy dog";
+    let mut interner = MAInterner::new();
+    let s = interner.intern_static(TEXT);
+    assert_eq!(s.get_mode(),"static");
+    assert_eq!(s.as_ptr(),TEXT.as_ptr());
+    let s2 = interner.intern("the quick brown fox jumped over the lazy dog");
+    assert_eq!(s2.as_ptr(),TEXT.as_ptr());
+
+    let s = MAString::from_string(String::from("the quick brown fox jumped over the lazy cat"));
+    assert_eq!(s.get_mode(),"unique");
+    let s = interner.intern_mas(s);
+    assert_eq!(s.get_mode(),"cbowned (shared)");
+    let s2 = interner.intern_static("the quick brown fox jumped over the lazy cat");
+    assert_eq!(s2.as_ptr(),s.as_ptr());
+}
+
+#[test]
+fn test_intern_mas_copy() {
+    let mut interner = MAInterner::new();
+    // a slice of a larger string is copied.
+    let big = MAString::from_slice("the quick brown fox jumped over the lazy dog and the lazy cat");
+    let slice = big.slice(4..);
+    let s = interner.intern_mas(slice.clone());
+    assert_eq!(s,"quick brown fox jumped over the lazy dog and the lazy cat");
+    assert_ne!(s.as_ptr(),slice.as_ptr());
+    let prefix = big.slice(..44);
+    let s = interner.intern_mas(prefix.clone());
+    assert_eq!(s,"the quick brown fox jumped over the lazy dog");
+    assert_ne!(s.as_ptr(),prefix.as_ptr());
+
+    // so is a string with a lot of spare capacity.
+    let mut st = String::with_capacity(1000);
+    st.push_str("the quick brown fox jumped over the lazy cow");
+    let ptr = st.as_ptr();
+    let s = interner.intern_mas(MAString::from_string(st));
+    assert_ne!(s.as_ptr(),ptr);
+    assert!(s.capacity() < 1000);
+
+    // but a string that uses it's whole buffer is not.
+    let st = MAString::from_slice("the quick brown fox jumped over the lazy pig");
+    let ptr = st.as_ptr();
+    let s = interner.intern_mas(st);
+    assert_eq!(s.as_ptr(),ptr);
+    assert_eq!(interner.len(),4);
+}
+
+#[test]
+fn test_remove_unused() {
+    let mut interner = MAInterner::new();
+    let s = interner.intern("the quick brown fox jumped over the lazy dog");
+    let s2 = interner.intern("the quick brown fox jumped over the lazy cat");
+    interner.intern_static("the quick brown fox jumped over the lazy cow");
+    assert_eq!(interner.len(),3);
+    drop(s2);
+    interner.remove_unused();
+    assert_eq!(interner.len(),2);
+    assert_mode!(s,"cbinline (shared)");
+    assert!(interner.get("the quick brown fox jumped over the lazy cat").is_none());
+    interner.clear();
+    assert!(interner.is_empty());
+    assert_mode!(s,"cbinline (unique)");
+}
+
+#[cfg(feature = "global-interner")]
+#[test]
+fn test_global() {
+    let s = MAInterner::global().lock().unwrap().intern("the quick brown fox jumped over the lazy dog");
+    let s2 = std::thread::spawn(|| {
+        MAInterner::global().lock().unwrap().intern("the quick brown fox jumped over the lazy dog")
+    }).join().unwrap();
+    assert_eq!(s2.as_ptr(),s.as_ptr());
+}
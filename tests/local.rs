@@ -0,0 +1,156 @@
+#![allow(clippy::bool_assert_comparison)]
+// the Hash impls only look at the string contents, not the interior mutable control block pointer.
+#![allow(clippy::mutable_key_type)]
+use mastring::MALocalString;
+use mastring::MALocalByteString;
+use mastring::MAString;
+use mastring::MAByteString;
+use mastring::MAStringBuilder;
+use mastring::mals;
+use mastring::malbs;
+#[cfg(miri)]
+use core::mem;
+#[cfg(miri)]
+use core::sync::atomic::AtomicPtr;
+use std::collections::HashSet;
+
+#[cfg(test)]
+macro_rules! assert_mode {
+    ($s:expr, $expectedmode:expr) => {
+        for _n in 1..=1 { // loop for break, since block labels are not supported in rust 1.63
+            let mode = $s.get_mode();
+            let expectedmode = $expectedmode;
+            #[cfg(miri)]
+            if (($s.as_ptr() as usize) & (mem::align_of::<AtomicPtr<usize>>() - 1)) != 0 {
+                //miri sometimes gives us unaligned vecs, this can lead to
+                //control blocks not fitting inline. This should't break correctness, but
+                //it can result in strings being in a different mode from expected.
+                if (mode == "unique") && (expectedmode == "cbinline (unique)") { break }
+                if (mode == "cbowned (unique)") && (expectedmode == "cbinline (unique)") { break }
+                if (mode == "cbowned (shared)") && (expectedmode == "cbinline (shared)") { break }
+            }
+            assert_eq!(mode,expectedmode);
+        }
+    }
+}
+
+#[test]
+fn test_modes() {
+    let s = MALocalString::new();
+    assert_eq!(s,"");
+    assert_mode!(s,"short");
+    let s = MALocalString::from_slice("hello");
+    assert_mode!(s,"short");
+    let s = MALocalString::from_static("the quick brown fox jumped over the lazy dog");
+    assert_mode!(s,"static");
+    let s = MALocalString::from_slice("the quick brown fox jumped over the lazy dog");
+    assert_mode!(s,"cbinline (unique)");
+    let mut st = String::with_capacity(44);
+    st.push_str("the quick brown fox jumped over the lazy dog");
+    let s = MALocalString::from_string(st);
+    assert_mode!(s,"unique");
+    let s2 = s.clone();
+    assert_mode!(s,"cbowned (shared)");
+    assert_mode!(s2,"cbowned (shared)");
+}
+
+#[test]
+fn test_clone_and_drop() {
+    let s = MALocalString::from_slice("the quick brown fox jumped over the lazy dog");
+    let s2 = s.clone();
+    let s3 = s2.clone();
+    assert_eq!(s2.as_ptr(),s.as_ptr());
+    assert_eq!(s3.as_ptr(),s.as_ptr());
+    assert_mode!(s,"cbinline (shared)");
+    drop(s);
+    drop(s2);
+    assert_mode!(s3,"cbinline (unique)");
+    assert_eq!(s3,"the quick brown fox jumped over the lazy dog");
+
+    let s = MALocalByteString::from_vec(b"the quick brown fox jumped over the lazy dog".to_vec());
+    let s2 = s.clone();
+    drop(s);
+    assert_mode!(s2,"cbowned (unique)");
+    assert_eq!(s2,b"the quick brown fox jumped over the lazy dog");
+}
+
+#[test]
+fn test_modify() {
+    let mut s = MALocalString::from_slice("the quick brown fox jumped over the lazy dog");
+    let s2 = s.clone();
+    s += "!";
+    assert_eq!(s,"the quick brown fox jumped over the lazy dog!");
+    assert_eq!(s2,"the quick brown fox jumped over the lazy dog");
+    assert_ne!(s.as_ptr(),s2.as_ptr());
+    assert_mode!(s2,"cbinline (unique)");
+    let ptr = s.as_ptr();
+    s.push_str("!");
+    assert_eq!(s.as_ptr(),ptr);
+    s.make_ascii_uppercase();
+    assert_eq!(s,"THE QUICK BROWN FOX JUMPED OVER THE LAZY DOG!!");
+
+    let mut s = MALocalByteString::from_slice(b"the quick brown fox jumped over the lazy dog");
+    let s2 = s.clone();
+    s.clear();
+    assert_eq!(s,b"");
+    assert_eq!(s2,b"the quick brown fox jumped over the lazy dog");
+    assert_eq!(s2.into_vec(),b"the quick brown fox jumped over the lazy dog");
+}
+
+#[test]
+fn test_conversions() {
+    let s = MAString::from_slice("the quick brown fox jumped over the lazy dog");
+    let ptr = s.as_ptr();
+    let l = MALocalString::from_mas(s);
+    assert_eq!(l.as_ptr(),ptr);
+    let l2 = l.clone();
+    // l2 still shares the buffer, so converting l must copy.
+    let s = l.into_mas();
+    assert_ne!(s.as_ptr(),ptr);
+    let s2 = l2.into_mas();
+    assert_eq!(s2.as_ptr(),ptr);
+
+    // the MAString still shares the buffer, so converting it must copy.
+    let s3 = s2.clone();
+    let l = MALocalString::from_mas(s2);
+    assert_ne!(l.as_ptr(),s3.as_ptr());
+    assert_eq!(l,"the quick brown fox jumped over the lazy dog");
+    assert_eq!(s,"the quick brown fox jumped over the lazy dog");
+
+    let b = MAByteString::from_static(b"the quick brown fox jumped over the lazy dog");
+    let l = MALocalByteString::from_mabs(b);
+    assert_mode!(l,"static");
+    let b = l.into_mabs();
+    assert_mode!(b,"static");
+
+    let l = MALocalString::from_builder(MAStringBuilder::from_slice("the quick brown fox jumped over the lazy dog"));
+    assert_eq!(l.into_string(),"the quick brown fox jumped over the lazy dog");
+}
+
+#[test]
+fn test_macro() {
+    let s = mals!("the quick brown fox jumped over the lazy dog");
+    assert_mode!(s,"static");
+    let s = mals!(['a','b','c']);
+    assert_eq!(s,"abc");
+    let s = mals!(String::from("the quick brown fox jumped over the lazy dog"));
+    assert_eq!(s,"the quick brown fox jumped over the lazy dog");
+    let s2 = mals!(&s);
+    assert_eq!(s2.as_ptr(),s.as_ptr());
+    let s = malbs!(b"the quick brown fox jumped over the lazy dog");
+    assert_mode!(s,"static");
+    let s = malbs!([1,2,3]);
+    assert_eq!(s,&[1,2,3]);
+    let s = malbs!(vec![1,2,3]);
+    assert_eq!(s,&[1,2,3]);
+}
+
+#[test]
+fn test_sets() {
+    let mut set = HashSet::new();
+    set.insert(mals!("foo"));
+    set.insert(MALocalString::from_slice("the quick brown fox jumped over the lazy dog"));
+    assert!(set.contains("foo"));
+    assert!(set.contains("the quick brown fox jumped over the lazy dog"));
+    assert!(!set.contains("bar"));
+}
@@ -0,0 +1,152 @@
+// model checking of the reference counting with loom, run with
+// RUSTFLAGS="--cfg loom" cargo test --release --test loom
+#![cfg(loom)]
+use mastring::MAByteString;
+use mastring::MAString;
+use mastring::mas;
+use mastring::Mode;
+use loom::sync::Arc;
+use loom::thread;
+
+const DATA : &[u8] = b"the quick brown fox jumped over the lazy dog";
+
+// a uniquely owned string with no room for an inline control block.
+fn unique_no_room() -> MAByteString {
+    let mut v = Vec::with_capacity(DATA.len());
+    v.extend_from_slice(DATA);
+    MAByteString::from_vec(v)
+}
+
+// a uniquely owned string with room for an inline control block.
+fn unique_with_room() -> MAByteString {
+    let mut v = Vec::with_capacity(100);
+    v.extend_from_slice(DATA);
+    let mut s = MAByteString::from_vec(v);
+    // using the space taken by the inline control block makes the string
+    // unique, with room after the data.
+    s.reserve(s.capacity() + 1);
+    s
+}
+
+fn clone_of_unique(make: fn() -> MAByteString, expected: Mode) {
+    loom::model(move || {
+        let s = Arc::new(make());
+        assert_eq!(s.mode(),Mode::Unique);
+        let s2 = s.clone();
+        let t = thread::spawn(move || {
+            let c = s2.as_ref().clone();
+            assert_eq!(c,DATA);
+            c
+        });
+        let c = s.as_ref().clone();
+        let c2 = t.join().unwrap();
+        assert_eq!(s.mode(),expected);
+        assert_eq!(s.strong_count(),3);
+        assert_eq!(c.as_ptr(),c2.as_ptr());
+        drop(c);
+        assert_eq!(c2.strong_count(),2);
+    });
+}
+
+#[test]
+fn test_clone_of_unique_cbowned() {
+    clone_of_unique(unique_no_room,Mode::CbOwned);
+}
+
+#[test]
+fn test_clone_of_unique_cbinline() {
+    clone_of_unique(unique_with_room,Mode::CbInline);
+}
+
+#[test]
+fn test_drop_race() {
+    loom::model(|| {
+        let s = MAByteString::from_slice(DATA);
+        let s2 = s.clone();
+        let s3 = s2.slice(4..);
+        let t = thread::spawn(move || {
+            assert_eq!(s2,DATA);
+            drop(s2);
+        });
+        drop(s);
+        assert_eq!(s3,&DATA[4..]);
+        t.join().unwrap();
+        assert!(s3.is_unique());
+    });
+}
+
+#[test]
+fn test_drop_race_cbowned() {
+    loom::model(|| {
+        let s = unique_no_room();
+        let s2 = s.clone();
+        let t = thread::spawn(move || drop(s2));
+        drop(s);
+        t.join().unwrap();
+    });
+}
+
+#[test]
+fn test_make_unique_with_clones() {
+    loom::model(|| {
+        let mut s = MAByteString::from_slice(DATA);
+        let s2 = s.clone();
+        let t = thread::spawn(move || {
+            let s3 = s2.clone();
+            assert_eq!(s3,DATA);
+            drop(s2);
+            s3
+        });
+        // whether this copies depends on whether the other thread still
+        // holds a reference.
+        s.push_slice(b"!");
+        let s3 = t.join().unwrap();
+        assert_eq!(&s[..DATA.len()],DATA);
+        assert_eq!(s.len(),DATA.len() + 1);
+        assert_eq!(s3,DATA);
+        assert!(s.is_unique());
+    });
+}
+
+#[test]
+fn test_make_unique_with_weak() {
+    loom::model(|| {
+        let mut s = MAByteString::from_slice(DATA);
+        let w = s.downgrade();
+        let t = thread::spawn(move || {
+            if let Some(s2) = w.upgrade() {
+                assert_eq!(s2,DATA);
+            }
+        });
+        s[0] = b'T';
+        assert_eq!(&s[1..],&DATA[1..]);
+        t.join().unwrap();
+    });
+}
+
+#[test]
+fn test_static() {
+    loom::model(|| {
+        // a shared string, so that there are other atomics in the model.
+        let shared = MAByteString::from_slice(DATA);
+        let shared2 = shared.clone();
+        let s = MAByteString::from_static(DATA);
+        let s2 = s.clone();
+        let t = thread::spawn(move || {
+            assert_eq!(s2.mode(),Mode::Static);
+            assert_eq!(s2.capacity(),0);
+            s2.layout();
+            drop(shared2);
+        });
+        let ms = mas!("the quick brown fox jumped over the lazy dog");
+        assert_eq!(ms.mode(),Mode::Static);
+        assert_eq!(ms.capacity(),0);
+        ms.layout();
+        assert_eq!(s.capacity(),0);
+        s.layout();
+        let c : MAString = ms.clone();
+        assert_eq!(c,ms);
+        t.join().unwrap();
+        assert!(shared.is_unique());
+    });
+}
@@ -0,0 +1,67 @@
+use mastring::MemoryReport;
+use mastring::MAString;
+use mastring::MAByteString;
+use mastring::MAStringBuilder;
+use mastring::MALocalString;
+use mastring::mas;
+use core::mem::size_of;
+
+#[test]
+fn test_heap_size() {
+    assert_eq!(mas!("the quick brown fox jumped over the lazy dog").heap_size(),0);
+    assert_eq!(MAString::from_slice("short").heap_size(),0);
+    let s = MAString::from_string(String::from("the quick brown fox jumped over the lazy dog"));
+    assert_eq!(s.get_mode(),"unique");
+    let cap = s.capacity();
+    assert_eq!(s.heap_size(),cap);
+    // cloning allocates a seperate control block.
+    let s2 = s.clone();
+    assert_eq!(s.get_mode(),"cbowned (shared)");
+    assert_eq!(s.heap_size(),cap + size_of::<usize>() * 3);
+    assert_eq!(s2.heap_size(),s.heap_size());
+    let s = MAByteString::with_capacity(100);
+    #[cfg(miri)]
+    // miri sometimes gives us unaligned vecs, which can leave no room for
+    // an inline control block, so the string is unique and the heap size
+    // is the capacity.
+    assert!(s.heap_size() >= s.capacity());
+    #[cfg(not(miri))]
+    assert!(s.heap_size() > s.capacity());
+    let b = MAStringBuilder::with_capacity(100);
+    assert_eq!(b.heap_size(),b.capacity());
+    assert_eq!(MAStringBuilder::from_slice("short").heap_size(),0);
+    let l = MALocalString::from_slice("the quick brown fox jumped over the lazy dog");
+    assert!(l.heap_size() >= l.capacity());
+}
+
+#[test]
+fn test_memory_report() {
+    let s = MAString::from_slice("the quick brown fox jumped over the lazy dog");
+    let mut strings = vec![s.clone(), s.clone(), s.slice(4..), mas!("static"), MAString::from_slice("short")];
+    strings.push(MAString::from_slice("the quick brown fox jumped over the lazy cat"));
+    let report: MemoryReport = strings.iter().collect();
+    assert_eq!(report.strings(),6);
+    assert_eq!(report.buffers(),2);
+    assert_eq!(report.heap_size(),s.heap_size() + strings[5].heap_size());
+    assert_eq!(report.naive_heap_size(),s.heap_size() * 3 + strings[5].heap_size());
+
+    let mut report = MemoryReport::new();
+    let b = MAByteString::from_slice(b"the quick brown fox jumped over the lazy dog");
+    report.add_mabs(&b);
+    report.add_mabs(&b.clone());
+    report.add_mas(&s);
+    assert_eq!(report.strings(),3);
+    assert_eq!(report.buffers(),2);
+    assert_eq!(report.heap_size(),b.heap_size() + s.heap_size());
+
+    // a uniquely owned string is counted once, along with the control block
+    // allocated when it is cloned after being added.
+    let mut report = MemoryReport::new();
+    let u = MAString::from_string(String::from("the quick brown fox jumped over the lazy dog"));
+    report.add_mas(&u);
+    let u2 = u.clone();
+    report.add_mas(&u2);
+    assert_eq!(report.buffers(),1);
+    assert_eq!(report.heap_size(),u.heap_size());
+    assert_eq!(report.heap_size(),u.capacity() + size_of::<usize>() * 3);
+}
@@ -0,0 +1,17 @@
+#![no_std]
+use mastring::MAString;
+use mastring::MAStringBuilder;
+use mastring::mas;
+
+#[no_mangle]
+pub extern "C" fn mastring_no_std_link() -> usize {
+    let mut b = MAStringBuilder::from_slice("hello");
+    b.push_str(" world");
+    let s = MAString::from(b);
+    s.len() + mas!("the quick brown fox jumped over the lazy dog").len()
+}
+
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
@@ -0,0 +1,91 @@
+#![allow(clippy::bool_assert_comparison)]
+// these tests only use short and static strings, so they run with or without
+// the alloc feature.
+use mastring::MAString;
+use mastring::MAByteString;
+use mastring::MAStringBuilder;
+use mastring::mas;
+use mastring::mabs;
+use core::cmp::Ordering;
+use core::hash::Hash;
+use core::hash::Hasher;
+use core::mem;
+use std::collections::hash_map::DefaultHasher;
+
+fn hash<T: Hash + ?Sized>(v: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    v.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[test]
+fn test_short_and_static() {
+    let s = mas!("the quick brown fox jumped over the lazy dog");
+    assert_eq!(s.get_mode(),"static");
+    assert_eq!(s,"the quick brown fox jumped over the lazy dog");
+    let s2 = s.clone();
+    assert_eq!(s2.get_mode(),"static");
+    assert_eq!(s2.as_ptr(),s.as_ptr());
+    let s = MAString::from_slice("hello");
+    assert_eq!(s.get_mode(),"short");
+    let s2 = mas!(['h','e','l','l','o']);
+    assert_eq!(s,s2);
+    assert_eq!(hash(&s),hash("hello"));
+    assert_eq!(s.cmp(&mas!("world")),Ordering::Less);
+    let b = mabs!([1,2,3]);
+    assert_eq!(b,&[1,2,3]);
+    let b = MAByteString::from_slice(b"bytes");
+    assert_eq!(b.get_mode(),"short");
+    assert_eq!(&b[1..3],b"yt");
+}
+
+#[test]
+fn test_join() {
+    let s = mas!(", ").join(["foo","bar","baz"]);
+    assert_eq!(s,"foo, bar, baz");
+    assert_eq!(s.get_mode(),"short");
+    let s = mabs!(b"-").join([&b"a"[..],b"b"]);
+    assert_eq!(s,b"a-b");
+}
+
+#[test]
+fn test_growth() {
+    let shortlen = mem::size_of::<MAString>() - 1;
+    let mut s = MAString::new();
+    for _ in 0..shortlen {
+        s.try_push_str("a").unwrap();
+    }
+    assert_eq!(s.get_mode(),"short");
+    let mut b = MAStringBuilder::new();
+    b.try_push_str(&s).unwrap();
+    assert!(MAString::try_with_capacity(shortlen).is_ok());
+    let result = s.try_push_str("a");
+    #[cfg(not(feature = "alloc"))]
+    {
+        assert!(result.is_err());
+        assert_eq!(s.len(),shortlen);
+        assert!(b.try_push_str("a").is_err());
+        assert!(MAString::try_from_slice("the quick brown fox jumped over the lazy dog").is_err());
+        assert!(MAString::try_with_capacity(shortlen + 1).is_err());
+        assert!(mas!("the quick brown fox jumped over the lazy dog").try_reserve(100).is_err());
+    }
+    #[cfg(feature = "alloc")]
+    {
+        assert!(result.is_ok());
+        assert_eq!(s.len(),shortlen + 1);
+    }
+}
+
+// cargo test always links std, so check that a no_std staticlib using
+// mastring without the alloc feature links without a global allocator, see
+// tests/no_std.
+#[test]
+#[cfg_attr(miri, ignore)]
+fn test_no_std_link() {
+    let manifest = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/no_std/Cargo.toml");
+    let status = std::process::Command::new(env!("CARGO"))
+        .args(["build", "--manifest-path", manifest, "--target-dir", env!("CARGO_TARGET_TMPDIR")])
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
@@ -1,5 +1,9 @@
+#![allow(clippy::bool_assert_comparison)]
+// the Hash impls only look at the string contents, not the interior mutable control block pointer.
+#![allow(clippy::mutable_key_type)]
 use mastring::MAString;
 use mastring::MAStringBuilder;
+use mastring::Mode;
 use core::mem;
 use core::ops::Deref;
 use core::ops::DerefMut;
@@ -159,6 +163,12 @@ fn test_reserve() {
     assert!(s.capacity() <= "the quick brown fox jumped over the lazy dog".len() + 50);
     //s2 now owns the buffer fomerly owned by s
     assert_mode!(s2,"cbinline (unique)");
+    #[cfg(miri)]
+    // with an unaligned vec from miri there may be no room for the control
+    // block after the reserved space, in which case the clone places it
+    // straight after the data.
+    assert!(s2.capacity() >= 100 - std::mem::size_of::<usize>());
+    #[cfg(not(miri))]
     assert!(s2.capacity() >= 100);
     assert!(s2.capacity() <= 150);
 
@@ -226,7 +236,8 @@ fn test_capacity() {
     let s = MAString::from_string(v);
     assert_mode!(s,"cbinline (unique)");
     assert!(s.capacity() < veccap);
-    assert!(s.capacity() >= veccap - mem::size_of::<usize>()*2);
+    // the control block is three words, plus up to a word of alignment padding.
+    assert!(s.capacity() >= veccap - mem::size_of::<usize>()*4);
 }
 
 #[test]
@@ -405,6 +416,7 @@ fn test_deref_mut() {
 }
 
 #[test]
+#[allow(clippy::assign_op_pattern)] // deliberately testing Add as well as AddAssign
 fn test_add_and_eq() {
     let mut s  = MAString::from_static("test");
     s = s + "foo";
@@ -596,3 +608,216 @@ fn test_join() {
     let s = mas!(",").join(["1","2","3","4","5","6","7","8","9","0"]);
     assert_eq!(s,"1,2,3,4,5,6,7,8,9,0");
 }
+
+#[test]
+fn test_slice() {
+    let s = MAString::from_slice("the quick brown fox jumped over the lazy dog");
+    assert_mode!(s,"cbinline (unique)");
+    let s2 = s.slice(4..9);
+    assert_eq!(s2,"quick");
+    assert_mode!(s2,"short");
+    let s2 = s.slice(4..);
+    assert_eq!(s2,"quick brown fox jumped over the lazy dog");
+    assert_eq!(s2.as_ptr(),s[4..].as_ptr());
+    assert_mode!(s,"cbinline (shared)");
+    assert_mode!(s2,"cbinline (shared)");
+    drop(s);
+    assert_mode!(s2,"cbinline (unique)");
+    assert_eq!(s2.into_string(),"quick brown fox jumped over the lazy dog");
+
+    let s = MAString::from_static("the quick brown fox jumped over the lazy dog");
+    let s2 = s.slice(..=39);
+    assert_eq!(s2,"the quick brown fox jumped over the lazy");
+    assert_mode!(s2,"static");
+}
+
+#[test]
+#[should_panic]
+fn test_slice_not_char_boundary() {
+    let s = MAString::from_slice("the quick brown fox jumped over the lazy dög");
+    let _ = s.slice(..s.len()-2);
+}
+
+#[test]
+fn test_split_shared() {
+    let s = MAString::from_slice("the quick brown fox jumped over,the lazy dog jumped over the fox,,end");
+    let v: Vec<MAString> = s.split_shared(',').collect();
+    assert_eq!(v,["the quick brown fox jumped over","the lazy dog jumped over the fox","","end"]);
+    assert_mode!(v[0],"short");
+    assert_eq!(v[1].as_ptr(),s[32..].as_ptr());
+    assert_mode!(v[1],"cbinline (shared)");
+    assert_mode!(v[3],"short");
+
+    let v: Vec<MAString> = s.split_shared(" the ").collect();
+    assert_eq!(v,s.split(" the ").collect::<Vec<_>>());
+    let v: Vec<MAString> = s.split_shared(&[',','j'][..]).collect();
+    assert_eq!(v,s.split(&[',','j'][..]).collect::<Vec<_>>());
+    let v: Vec<MAString> = s.split_shared(['q','z']).collect();
+    assert_eq!(v,s.split(['q','z']).collect::<Vec<_>>());
+    let v: Vec<MAString> = s.split_shared(|c: char| c == 'q' || c == 'z').collect();
+    assert_eq!(v,["the ","uick brown fox jumped over,the la","y dog jumped over the fox,,end"]);
+    let v: Vec<MAString> = mas!("añb").split_shared("").collect();
+    assert_eq!(v,"añb".split("").collect::<Vec<_>>());
+    let v: Vec<MAString> = mas!("").split_shared(',').collect();
+    assert_eq!(v,[""]);
+
+    let (a,b) = s.split_once_shared(',').unwrap();
+    assert_eq!(a,"the quick brown fox jumped over");
+    assert_eq!(b,"the lazy dog jumped over the fox,,end");
+    assert_eq!(b.as_ptr(),s[32..].as_ptr());
+    assert!(s.split_once_shared('#').is_none());
+}
+
+#[test]
+fn test_lines_shared() {
+    let s = MAString::from_slice("the quick brown fox jumped\r\nover the lazy dog\n\nend\n");
+    let v: Vec<MAString> = s.lines_shared().collect();
+    assert_eq!(v,["the quick brown fox jumped","over the lazy dog","","end"]);
+    assert_eq!(v,s.lines().collect::<Vec<_>>());
+    let s = MAString::from_slice("the quick brown fox jumped over the lazy dog\nno newline at end");
+    assert_eq!(s.lines_shared().collect::<Vec<_>>(),s.lines().collect::<Vec<_>>());
+    assert_eq!(mas!("").lines_shared().count(),0);
+}
+
+#[test]
+fn test_split_whitespace_shared() {
+    let s = MAString::from_slice("  the quick brown fox\u{3000}jumped over the lazy dog\t\n");
+    let v: Vec<MAString> = s.split_whitespace_shared().collect();
+    assert_eq!(v,s.split_whitespace().collect::<Vec<_>>());
+    assert_eq!(mas!(" \t ").split_whitespace_shared().count(),0);
+    let s = MAString::from_slice("the_quick_brown_fox_jumped_over_the_lazy_dog dog");
+    let v: Vec<MAString> = s.split_whitespace_shared().collect();
+    assert_eq!(v,["the_quick_brown_fox_jumped_over_the_lazy_dog","dog"]);
+    assert_mode!(v[0],"cbinline (shared)");
+}
+
+#[test]
+fn test_downgrade() {
+    let s = MAString::from_slice("the quick brown fox jumped over the lazy dog");
+    let w = s.downgrade();
+    let s2 = w.upgrade().unwrap();
+    assert_eq!(s2,"the quick brown fox jumped over the lazy dog");
+    assert_eq!(s2.as_ptr(),s.as_ptr());
+    drop(s);
+    assert_eq!(w.clone().upgrade().unwrap(),"the quick brown fox jumped over the lazy dog");
+    drop(s2);
+    assert!(w.upgrade().is_none());
+
+    let s = MAString::from_slice("hello");
+    let w = s.downgrade();
+    drop(s);
+    assert_eq!(w.upgrade().unwrap(),"hello");
+}
+
+#[test]
+fn test_try() {
+    let mut s = MAString::try_from_slice("the quick brown fox jumped over the lazy dog").unwrap();
+    assert_eq!(s,"the quick brown fox jumped over the lazy dog");
+    assert!(s.try_reserve(usize::MAX).is_err());
+    s.try_push_str("!").unwrap();
+    assert_eq!(s,"the quick brown fox jumped over the lazy dog!");
+    let s2 = s.try_clone().unwrap();
+    assert_eq!(s2.as_ptr(),s.as_ptr());
+    let mut s = MAString::try_with_capacity(100).unwrap();
+    assert!(s.capacity() >= 100);
+    s.try_push_str("hello").unwrap();
+    assert_eq!(s,"hello");
+}
+
+#[test]
+fn test_mode_and_counts() {
+    let s = mas!("the quick brown fox jumped over the lazy dog");
+    assert_eq!(s.mode(),Mode::Static);
+    assert_eq!(s.as_static(),Some("the quick brown fox jumped over the lazy dog"));
+    assert_eq!(s.is_unique(),false);
+    let s = MAString::from_slice("the quick brown fox jumped over the lazy dog");
+    assert_eq!(s.as_static(),None);
+    assert_eq!(s.strong_count(),1);
+    assert_eq!(s.is_unique(),true);
+    let s2 = s.clone();
+    assert!(s.mode() == Mode::CbInline || s.mode() == Mode::CbOwned);
+    assert_eq!(s2.strong_count(),2);
+    assert_eq!(s2.is_unique(),false);
+    assert!(MAString::ptr_eq(&s,&s2));
+    drop(s2);
+    assert_eq!(s.strong_count(),1);
+    assert_eq!(s.is_unique(),true);
+    let s = MAString::from_slice("short");
+    assert_eq!(s.mode(),Mode::Short);
+    assert_eq!(s.is_unique(),true);
+}
+
+#[test]
+fn test_try_unwrap() {
+    let s = MAString::from_slice("the quick brown fox jumped over the lazy dog");
+    let ptr = s.as_ptr();
+    let s2 = s.clone();
+    let s = s.into_string_if_unique().unwrap_err();
+    let s = s.try_unwrap().unwrap_err();
+    drop(s2);
+    let b = s.try_unwrap().unwrap();
+    assert_eq!(b,"the quick brown fox jumped over the lazy dog");
+    assert_eq!(b.as_ptr(),ptr);
+    let s = MAString::from_builder(b);
+    let st = s.into_string_if_unique().unwrap();
+    assert_eq!(st,"the quick brown fox jumped over the lazy dog");
+    assert_eq!(st.as_ptr(),ptr);
+    let s = mas!("the quick brown fox jumped over the lazy dog");
+    assert_eq!(s.try_unwrap().unwrap_err().mode(),Mode::Static);
+    let s = MAString::from_slice("the quick brown fox jumped over the lazy dog").slice(4..);
+    let s = s.into_string_if_unique().unwrap_err();
+    assert_eq!(s.try_unwrap().unwrap_err(),"quick brown fox jumped over the lazy dog");
+}
+
+#[test]
+fn test_make_shared() {
+    let s = MAString::from_string_shared(String::from("the quick brown fox jumped over the lazy dog"));
+    assert_mode!(s,"cbinline (unique)");
+    let mut s = MAString::from_string(String::from("the quick brown fox jumped over the lazy dog"));
+    assert_mode!(s,"unique");
+    s.make_shared();
+    assert_mode!(s,"cbinline (unique)");
+    let b = MAStringBuilder::with_capacity(100);
+    let mut b = b + "the quick brown fox jumped over the lazy dog";
+    while b.len() + 10 <= 100 {
+        b.push_str("0123456789");
+    }
+    let s = MAString::from_builder(b);
+    assert_mode!(s,"cbinline (unique)");
+
+    let s = MAString::with_capacity_shared(100);
+    assert_mode!(s,"cbinline (unique)");
+    assert!(s.capacity() >= 100);
+    // a builder that has grown beyond it's capacity has no room for an
+    // inline control block, from_builder_shared reallocates once.
+    let mut b = MAStringBuilder::with_capacity(64);
+    while b.len() <= 64 {
+        b.push_str("a");
+    }
+    assert_eq!(b.len(),65);
+    let s = MAString::from_builder_shared(b);
+    assert_mode!(s,"cbinline (unique)");
+    let s2 = s.clone();
+    assert_mode!(s2,"cbinline (shared)");
+    let mut b = MAStringBuilder::with_capacity(64);
+    b.push_str("the quick brown fox jumped over the lazy dog");
+    let ptr = b.as_ptr();
+    let s = MAString::from_builder_shared(b);
+    assert_mode!(s,"cbinline (unique)");
+    assert_eq!(s.as_ptr(),ptr);
+}
+
+#[test]
+fn test_shrink() {
+    let mut s = MAString::with_capacity(1000);
+    s.push_str("the quick brown fox jumped over the lazy dog");
+    s.shrink_to_fit();
+    assert!(s.capacity() < 100);
+    assert_eq!(s,"the quick brown fox jumped over the lazy dog");
+    let mut s = MAString::from_slice("hello") + " world";
+    s.reserve(100);
+    assert_mode!(s,"cbinline (unique)");
+    s.shrink_to_fit();
+    assert_mode!(s,"short");
+    assert_eq!(s,"hello world");
+}
@@ -1,6 +1,10 @@
+#![allow(clippy::bool_assert_comparison)]
+// the Hash impls only look at the string contents, not the interior mutable control block pointer.
+#![allow(clippy::mutable_key_type)]
 use mastring::MAStringBuilder;
 use mastring::MAByteStringBuilder;
 use mastring::MAString;
+use mastring::Mode;
 use core::mem;
 use core::ops::Deref;
 use core::ops::DerefMut;
@@ -119,7 +123,8 @@ fn test_reserve() {
     assert_eq!(s,"the quick brown fox jumped over the lazy dog");
     assert_eq!(s.get_mode(),"unique");
     assert!(s.capacity() >= 200);
-    assert!(s.capacity() <= 250);
+    // growth is exponential, and also leaves room for a control block.
+    assert!(s.capacity() <= 320);
 }
 
 #[test]
@@ -242,6 +247,7 @@ fn test_deref_mut() {
 }
 
 #[test]
+#[allow(clippy::assign_op_pattern)] // deliberately testing Add as well as AddAssign
 fn test_add_and_eq() {
     let mut s  = MAStringBuilder::from_slice("test");
     s = s + "foo";
@@ -399,3 +405,44 @@ fn test_join() {
     let s = masb!(",").join(["1","2","3","4","5","6","7","8","9","0"]);
     assert_eq!(s,"1,2,3,4,5,6,7,8,9,0");
 }
+
+#[test]
+fn test_try() {
+    let mut s = MAStringBuilder::try_from_slice("the quick brown fox jumped over the lazy dog").unwrap();
+    assert_eq!(s,"the quick brown fox jumped over the lazy dog");
+    assert!(s.try_reserve(usize::MAX).is_err());
+    s.try_push_str("!").unwrap();
+    assert_eq!(s,"the quick brown fox jumped over the lazy dog!");
+    let s2 = s.try_clone().unwrap();
+    assert_eq!(s2,s);
+    let mut s = MAStringBuilder::try_with_capacity(100).unwrap();
+    assert!(s.capacity() >= 100);
+    s.try_push_str("hello").unwrap();
+    assert_eq!(s,"hello");
+}
+
+#[test]
+fn test_mode_and_counts() {
+    let s = MAStringBuilder::from_slice("short");
+    assert_eq!(s.mode(),Mode::Short);
+    let s = MAStringBuilder::from_slice("the quick brown fox jumped over the lazy dog");
+    assert_eq!(s.mode(),Mode::Unique);
+    assert_eq!(s.strong_count(),1);
+    assert_eq!(s.is_unique(),true);
+    assert_eq!(s.as_static(),None);
+    assert!(MAStringBuilder::ptr_eq(&s,&s));
+    assert!(!MAStringBuilder::ptr_eq(&s,&s.clone()));
+}
+
+#[test]
+fn test_shrink() {
+    let mut s = MAStringBuilder::with_capacity(1000);
+    s.push_str("hello world");
+    assert_eq!(s.get_mode(),"unique");
+    s.shrink_to(100);
+    assert_eq!(s.get_mode(),"unique");
+    assert!(s.capacity() >= 100);
+    s.shrink_to_fit();
+    assert_eq!(s.get_mode(),"short");
+    assert_eq!(s,"hello world");
+}
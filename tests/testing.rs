@@ -0,0 +1,65 @@
+use mastring::MAByteString;
+use mastring::MAByteStringBuilder;
+use mastring::MAString;
+use mastring::MAStringBuilder;
+use mastring::Mode;
+use mastring::testing::force_mode;
+use mastring::testing::MODES;
+use mastring::assert_mode;
+use core::mem::size_of;
+
+#[test]
+fn test_force_mode() {
+    for data in [&b""[..], b"short", b"the quick brown fox jumped over the lazy dog"] {
+        for mode in MODES {
+            if mode == Mode::Short && data.len() >= size_of::<MAByteString>() { continue }
+            // static strings are made by leaking a copy, which miri reports.
+            if cfg!(miri) && mode == Mode::Static { continue }
+            let mut s = force_mode(MAByteString::from_slice(data), mode);
+            assert_eq!(s.mode(),mode);
+            assert_eq!(s,data);
+            #[cfg(any(debug_assertions, feature = "check-invariants"))]
+            s.check_invariants();
+            let s2 = s.clone();
+            #[cfg(any(debug_assertions, feature = "check-invariants"))]
+            s2.check_invariants();
+            s.push_slice(b"!");
+            assert_eq!(&s[..data.len()],data);
+            assert_eq!(s2,data);
+        }
+    }
+    let s = force_mode(MAString::from_slice("hello"),Mode::CbInline);
+    assert_mode!(s,"cbinline (unique)");
+    let s2 = s.clone();
+    assert_mode!(s2,"cbinline (shared)");
+    let s = force_mode(MAString::from_slice("hello"),Mode::CbOwned);
+    assert_mode!(s,"cbowned (unique)");
+    let s = force_mode(MAString::from_slice("hello"),Mode::Unique);
+    assert_mode!(s,"unique");
+    let s2 = s.clone();
+    assert_mode!(s2,"cbowned (shared)");
+}
+
+#[test]
+fn test_force_mode_builder() {
+    let mut b = force_mode(MAByteStringBuilder::from_slice(b"hello"),Mode::Unique);
+    assert_mode!(b,"unique");
+    #[cfg(any(debug_assertions, feature = "check-invariants"))]
+    b.check_invariants();
+    b.push_slice(b" world");
+    assert_eq!(b,b"hello world");
+    let b = force_mode(MAStringBuilder::from_slice("hello"),Mode::Short);
+    assert_mode!(b,"short");
+}
+
+#[test]
+#[should_panic(expected = "builders can only be short or unique")]
+fn test_force_mode_builder_shared() {
+    force_mode(MAStringBuilder::from_slice("hello"),Mode::CbInline);
+}
+
+#[test]
+#[should_panic(expected = "string too long to be short")]
+fn test_force_mode_too_long() {
+    force_mode(MAString::from_slice("the quick brown fox jumped over the lazy dog"),Mode::Short);
+}